@@ -0,0 +1,304 @@
+//! Pairwise alignment: Needleman-Wunsch (global) and Smith-Waterman (local), with affine gaps (Gotoh).
+//! Proteins are aligned to compare them to their reference, nucleotide sequences to call variants.
+//! As in BLAST, a gap of length k costs gap_open + k * gap_extend.
+
+use serde::{Deserialize, Serialize};
+
+use crate::amino_acid::AminoAcid;
+use crate::nucleotide::Nucleotide;
+use crate::scoring::ScoringMatrix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlignmentMode {
+    /// Needleman-Wunsch, end to end.
+    Global,
+    /// Smith-Waterman, best matching region only.
+    Local,
+    /// No alignment, residue i against residue i.
+    Positional,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Scoring {
+    pub matrix: ScoringMatrix,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+}
+
+/// One column of an alignment: indices into the sample and reference proteins, None for a gap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Column {
+    pub sample: Option<usize>,
+    pub reference: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alignment {
+    pub score: i32,
+    /// In order along both proteins. A local alignment only covers the aligned region.
+    pub columns: Vec<Column>,
+}
+
+impl Alignment {
+    /// Nothing aligned, e.g. for a protein without a reference.
+    pub fn empty() -> Alignment
+    {
+        Alignment { score: 0, columns: Vec::new() }
+    }
+}
+
+pub fn align(sample: &[AminoAcid], reference: &[AminoAcid], mode: AlignmentMode, scoring: &Scoring) -> Alignment
+{
+    match mode {
+        AlignmentMode::Global => gotoh(sample, reference, scoring, false),
+        AlignmentMode::Local => gotoh(sample, reference, scoring, true),
+        AlignmentMode::Positional => positional(sample, reference, scoring),
+    }
+}
+
+pub fn positional(sample: &[AminoAcid], reference: &[AminoAcid], scoring: &Scoring) -> Alignment
+{
+    let mut score = 0;
+    let mut columns: Vec<Column> = Vec::new();
+    for i in 0..sample.len().max(reference.len()) {
+        let column = Column {
+            sample: if i < sample.len() { Some(i) } else { None },
+            reference: if i < reference.len() { Some(i) } else { None },
+        };
+        if let (Some(s), Some(r)) = (column.sample, column.reference) {
+            score += scoring.matrix.score(sample[s], reference[r]);
+        }
+        columns.push(column);
+    }
+    Alignment { score, columns }
+}
+
+// the three Gotoh states: last column is a pair, a sample residue against a gap, a reference residue against a gap
+const PAIR: u8 = 0;
+const INSERTION: u8 = 1;
+const DELETION: u8 = 2;
+// local alignment only: the pair starts the alignment
+const START: u8 = 3;
+
+const NEG: i32 = i32::MIN / 4;
+
+// first band of the traceback of two proteins, widened fourfold until it holds an optimal alignment
+const PROTEIN_BAND: usize = 32;
+
+fn best_of(pair: i32, insertion: i32, deletion: i32) -> (i32, u8)
+{
+    if pair >= insertion && pair >= deletion {
+        (pair, PAIR)
+    }
+    else if insertion >= deletion {
+        (insertion, INSERTION)
+    }
+    else {
+        (deletion, DELETION)
+    }
+}
+
+fn gotoh(sample: &[AminoAcid], reference: &[AminoAcid], scoring: &Scoring, local: bool) -> Alignment
+{
+    // identical proteins are by far the most common case
+    if !local && sample == reference {
+        return positional(sample, reference, scoring);
+    }
+    let gaps = (scoring.gap_open, scoring.gap_extend);
+    let pair = |i: usize, j: usize| scoring.matrix.score(sample[i], reference[j]);
+    let (n, m) = (sample.len(), reference.len());
+    // a traceback over the whole matrix is n * m bytes, 50 MB for two ORF1ab: take the best score in linear
+    // space first, then trace back over a band around the diagonal, as wide as needed to reach that score
+    let best = dynamic(n, m, pair, gaps, local, None, false).score;
+    let mut band = PROTEIN_BAND;
+    loop {
+        let alignment = dynamic(n, m, pair, gaps, local, Some(band), true);
+        if alignment.score >= best || band >= n.max(m) {
+            return alignment;
+        }
+        band *= 4;
+    }
+}
+
+/// Global alignment of two nucleotide sequences. Only cells within `band` of the diagonal (widened by the
+/// difference in length) are computed, which is enough for two genomes of the same species.
+/// Ambiguous bases match any base they can stand for.
+pub fn align_nucleotides(sample: &[Nucleotide], reference: &[Nucleotide], band: usize) -> Alignment
+{
+    // minimap2's scores for close sequences
+    let pair = |i: usize, j: usize| if sample[i] as u8 & reference[j] as u8 != 0 { 2 } else { -4 };
+    dynamic(sample.len(), reference.len(), pair, (4, 2), false, Some(band), true)
+}
+
+/// Local alignment of two nucleotide sequences, e.g. of a read to the stretch of the genome it comes from, with
+/// the scores and band of align_nucleotides: only the region where they match is aligned.
+pub fn align_nucleotides_local(sample: &[Nucleotide], reference: &[Nucleotide], band: usize) -> Alignment
+{
+    let pair = |i: usize, j: usize| if sample[i] as u8 & reference[j] as u8 != 0 { 2 } else { -4 };
+    dynamic(sample.len(), reference.len(), pair, (4, 2), true, Some(band), true)
+}
+
+// Gotoh's dynamic programming over an n x m matrix; `pair(i, j)` scores sample residue i against reference
+// residue j. With a band, row i only covers columns i + lowest ..= i + highest. Without `traceback`, only the
+// score is computed, in linear space, and the alignment has no columns.
+fn dynamic<F>(n: usize, m: usize, pair: F, gaps: (i32, i32), local: bool, band: Option<usize>, traceback: bool) -> Alignment
+    where F: Fn(usize, usize) -> i32
+{
+    let open = gaps.0 + gaps.1;
+    let extend = gaps.1;
+
+    let (lowest, highest): (isize, isize) = match band {
+        Some(band) => ((m as isize - n as isize).min(0) - band as isize, (m as isize - n as isize).max(0) + band as isize),
+        None => (-(n as isize), m as isize),
+    };
+    let columns_of = |i: usize| {
+        let first = (i as isize + lowest).max(0) as usize;
+        let last = (i as isize + highest).clamp(-1, m as isize);
+        first..(last + 1) as usize
+    };
+
+    // one byte of traceback per cell: bits 0-1 where the pair state came from,
+    // bits 2-3 the insertion state, bits 4-5 the deletion state; row i starts at row_start[i]
+    let mut row_start: Vec<usize> = Vec::with_capacity(n + 2);
+    row_start.push(0);
+    for i in 0..=n {
+        let last = row_start[i] + columns_of(i).len();
+        row_start.push(last);
+    }
+    let mut trace: Vec<u8> = vec![0; if traceback { row_start[n + 1] } else { 0 }];
+    let cell = |i: usize, j: usize| row_start[i] + j - columns_of(i).start;
+
+    let width = m + 1;
+    let mut pair_prev: Vec<i32> = vec![NEG; width + 1];
+    let mut ins_prev: Vec<i32> = vec![NEG; width + 1];
+    let mut del_prev: Vec<i32> = vec![NEG; width + 1];
+    pair_prev[0] = 0;
+    for j in columns_of(0).skip(1) {
+        del_prev[j] = if local { NEG } else { -open - (j as i32 - 1) * extend };
+        if traceback {
+            trace[cell(0, j)] = DELETION << 4;
+        }
+    }
+
+    let mut best = (0, 0, 0);
+    let mut pair_cur: Vec<i32> = vec![NEG; width + 1];
+    let mut ins_cur: Vec<i32> = vec![NEG; width + 1];
+    let mut del_cur: Vec<i32> = vec![NEG; width + 1];
+    for i in 1..=n {
+        let columns = columns_of(i);
+        if columns.start == 0 {
+            pair_cur[0] = NEG;
+            ins_cur[0] = if local { NEG } else { -open - (i as i32 - 1) * extend };
+            del_cur[0] = NEG;
+            if traceback {
+                trace[cell(i, 0)] = INSERTION << 2;
+            }
+        }
+        else {
+            // left of the band: still holds the row before the previous one
+            pair_cur[columns.start - 1] = NEG;
+            ins_cur[columns.start - 1] = NEG;
+            del_cur[columns.start - 1] = NEG;
+        }
+
+        for j in columns.start.max(1)..columns.end {
+            let (mut diagonal, mut from_pair) = best_of(pair_prev[j - 1], ins_prev[j - 1], del_prev[j - 1]);
+            if local && diagonal <= 0 {
+                diagonal = 0;
+                from_pair = START;
+            }
+            pair_cur[j] = diagonal + pair(i - 1, j - 1);
+
+            let (up, from_ins) = best_of(pair_prev[j] - open, ins_prev[j] - extend, del_prev[j] - open);
+            ins_cur[j] = up;
+            let (left, from_del) = best_of(pair_cur[j - 1] - open, ins_cur[j - 1] - open, del_cur[j - 1] - extend);
+            del_cur[j] = left;
+
+            if traceback {
+                trace[cell(i, j)] = from_pair | from_ins << 2 | from_del << 4;
+            }
+
+            if local && pair_cur[j] > best.0 {
+                best = (pair_cur[j], i, j);
+            }
+        }
+        // right of the band, read by the next row
+        pair_cur[columns.end] = NEG;
+        ins_cur[columns.end] = NEG;
+        del_cur[columns.end] = NEG;
+
+        std::mem::swap(&mut pair_prev, &mut pair_cur);
+        std::mem::swap(&mut ins_prev, &mut ins_cur);
+        std::mem::swap(&mut del_prev, &mut del_cur);
+    }
+
+    let (score, mut i, mut j, mut state) = if local {
+        (best.0, best.1, best.2, PAIR)
+    }
+    else {
+        let (score, state) = best_of(pair_prev[m], ins_prev[m], del_prev[m]);
+        (score, n, m, state)
+    };
+
+    if local && score <= 0 {
+        return Alignment::empty();
+    }
+    if !traceback {
+        return Alignment { score, columns: Vec::new() };
+    }
+    let mut columns: Vec<Column> = Vec::new();
+    while i > 0 || j > 0 {
+        let cell = trace[cell(i, j)];
+        match state {
+            PAIR => {
+                columns.push(Column { sample: Some(i - 1), reference: Some(j - 1) });
+                state = cell & 0b11;
+                i -= 1;
+                j -= 1;
+                if state == START {
+                    break;
+                }
+            }
+            INSERTION => {
+                columns.push(Column { sample: Some(i - 1), reference: None });
+                state = (cell >> 2) & 0b11;
+                i -= 1;
+            }
+            _ => {
+                columns.push(Column { sample: None, reference: Some(j - 1) });
+                state = (cell >> 4) & 0b11;
+                j -= 1;
+            }
+        }
+    }
+    columns.reverse();
+
+    Alignment { score, columns }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::protein;
+
+    const SCORING: Scoring = Scoring { matrix: ScoringMatrix::Blosum62, gap_open: 11, gap_extend: 1 };
+
+    #[test]
+    fn banded_traceback_reaches_the_best_score()
+    {
+        // as long as the reference, but 60 residues off its diagonal between a deletion and an insertion
+        let reference = protein(400, 1);
+        let sample: Vec<AminoAcid> = [&reference[..50], &reference[110..250], &protein(60, 2)[..], &reference[250..]].concat();
+        for &local in &[false, true] {
+            let pair = |i: usize, j: usize| SCORING.matrix.score(sample[i], reference[j]);
+            let full = dynamic(sample.len(), reference.len(), pair, (11, 1), local, None, true);
+            let narrow = dynamic(sample.len(), reference.len(), pair, (11, 1), local, Some(PROTEIN_BAND), true);
+            assert!(narrow.score < full.score);
+
+            let alignment = gotoh(&sample, &reference, &SCORING, local);
+            assert_eq!(alignment.score, full.score);
+            assert_eq!(alignment.columns, full.columns);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Amino acids, with their one- and three-letter codes and the physico-chemical properties used for drawing.
+
+use serde::{Deserialize, Serialize};
+
+use crate::nucleotide::Nucleotide;
+use crate::variant::Effect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AminoAcid {
+    Ala,
+    Arg,
+    Asn,
+    Asp,
+    Cys,
+    Gln,
+    Glu,
+    Gly,
+    His,
+    Ile,
+    Leu,
+    Lys,
+    Met,
+    Phe,
+    Pro,
+    Ser,
+    Thr,
+    Trp,
+    Tyr,
+    Val,
+    /// Translation stop, '*'.
+    Stop,
+    /// A codon that does not resolve to a single residue (ambiguous bases), 'X'.
+    Unknown,
+}
+
+/// Side chain class at physiological pH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SideChain {
+    Nonpolar,
+    Polar,
+    Acidic,
+    Basic,
+}
+
+/// The twenty standard residues, alphabetical by three-letter code.
+pub const STANDARD: [AminoAcid; 20] = [
+    AminoAcid::Ala, AminoAcid::Arg, AminoAcid::Asn, AminoAcid::Asp, AminoAcid::Cys,
+    AminoAcid::Gln, AminoAcid::Glu, AminoAcid::Gly, AminoAcid::His, AminoAcid::Ile,
+    AminoAcid::Leu, AminoAcid::Lys, AminoAcid::Met, AminoAcid::Phe, AminoAcid::Pro,
+    AminoAcid::Ser, AminoAcid::Thr, AminoAcid::Trp, AminoAcid::Tyr, AminoAcid::Val,
+];
+
+impl AminoAcid {
+    /// Reads a one-letter code, case-insensitively; '*' is Stop and 'X' is Unknown.
+    pub fn from_one_letter(c: char) -> Option<AminoAcid>
+    {
+        let aa = match c.to_ascii_uppercase() {
+            'A' => AminoAcid::Ala,
+            'R' => AminoAcid::Arg,
+            'N' => AminoAcid::Asn,
+            'D' => AminoAcid::Asp,
+            'C' => AminoAcid::Cys,
+            'Q' => AminoAcid::Gln,
+            'E' => AminoAcid::Glu,
+            'G' => AminoAcid::Gly,
+            'H' => AminoAcid::His,
+            'I' => AminoAcid::Ile,
+            'L' => AminoAcid::Leu,
+            'K' => AminoAcid::Lys,
+            'M' => AminoAcid::Met,
+            'F' => AminoAcid::Phe,
+            'P' => AminoAcid::Pro,
+            'S' => AminoAcid::Ser,
+            'T' => AminoAcid::Thr,
+            'W' => AminoAcid::Trp,
+            'Y' => AminoAcid::Tyr,
+            'V' => AminoAcid::Val,
+            '*' => AminoAcid::Stop,
+            'X' => AminoAcid::Unknown,
+            _ => return None,
+        };
+        Some(aa)
+    }
+
+    pub fn one_letter(self) -> char
+    {
+        match self {
+            AminoAcid::Ala => 'A',
+            AminoAcid::Arg => 'R',
+            AminoAcid::Asn => 'N',
+            AminoAcid::Asp => 'D',
+            AminoAcid::Cys => 'C',
+            AminoAcid::Gln => 'Q',
+            AminoAcid::Glu => 'E',
+            AminoAcid::Gly => 'G',
+            AminoAcid::His => 'H',
+            AminoAcid::Ile => 'I',
+            AminoAcid::Leu => 'L',
+            AminoAcid::Lys => 'K',
+            AminoAcid::Met => 'M',
+            AminoAcid::Phe => 'F',
+            AminoAcid::Pro => 'P',
+            AminoAcid::Ser => 'S',
+            AminoAcid::Thr => 'T',
+            AminoAcid::Trp => 'W',
+            AminoAcid::Tyr => 'Y',
+            AminoAcid::Val => 'V',
+            AminoAcid::Stop => '*',
+            AminoAcid::Unknown => 'X',
+        }
+    }
+
+    /// "Asp", "Gly", ...; "Ter" for stop and "Xaa" for unknown, as in HGVS.
+    pub fn three_letter(self) -> &'static str
+    {
+        match self {
+            AminoAcid::Ala => "Ala",
+            AminoAcid::Arg => "Arg",
+            AminoAcid::Asn => "Asn",
+            AminoAcid::Asp => "Asp",
+            AminoAcid::Cys => "Cys",
+            AminoAcid::Gln => "Gln",
+            AminoAcid::Glu => "Glu",
+            AminoAcid::Gly => "Gly",
+            AminoAcid::His => "His",
+            AminoAcid::Ile => "Ile",
+            AminoAcid::Leu => "Leu",
+            AminoAcid::Lys => "Lys",
+            AminoAcid::Met => "Met",
+            AminoAcid::Phe => "Phe",
+            AminoAcid::Pro => "Pro",
+            AminoAcid::Ser => "Ser",
+            AminoAcid::Thr => "Thr",
+            AminoAcid::Trp => "Trp",
+            AminoAcid::Tyr => "Tyr",
+            AminoAcid::Val => "Val",
+            AminoAcid::Stop => "Ter",
+            AminoAcid::Unknown => "Xaa",
+        }
+    }
+
+    /// Kyte-Doolittle hydropathy index, positive is hydrophobic.
+    pub fn hydropathy(self) -> f32
+    {
+        match self {
+            AminoAcid::Ala => 1.8,
+            AminoAcid::Arg => -4.5,
+            AminoAcid::Asn => -3.5,
+            AminoAcid::Asp => -3.5,
+            AminoAcid::Cys => 2.5,
+            AminoAcid::Gln => -3.5,
+            AminoAcid::Glu => -3.5,
+            AminoAcid::Gly => -0.4,
+            AminoAcid::His => -3.2,
+            AminoAcid::Ile => 4.5,
+            AminoAcid::Leu => 3.8,
+            AminoAcid::Lys => -3.9,
+            AminoAcid::Met => 1.9,
+            AminoAcid::Phe => 2.8,
+            AminoAcid::Pro => -1.6,
+            AminoAcid::Ser => -0.8,
+            AminoAcid::Thr => -0.7,
+            AminoAcid::Trp => -0.9,
+            AminoAcid::Tyr => -1.3,
+            AminoAcid::Val => 4.2,
+            AminoAcid::Stop | AminoAcid::Unknown => 0.0,
+        }
+    }
+
+    /// Average mass of the residue inside a chain, in daltons.
+    pub fn mass(self) -> f32
+    {
+        match self {
+            AminoAcid::Ala => 71.08,
+            AminoAcid::Arg => 156.19,
+            AminoAcid::Asn => 114.10,
+            AminoAcid::Asp => 115.09,
+            AminoAcid::Cys => 103.14,
+            AminoAcid::Gln => 128.13,
+            AminoAcid::Glu => 129.12,
+            AminoAcid::Gly => 57.05,
+            AminoAcid::His => 137.14,
+            AminoAcid::Ile => 113.16,
+            AminoAcid::Leu => 113.16,
+            AminoAcid::Lys => 128.17,
+            AminoAcid::Met => 131.19,
+            AminoAcid::Phe => 147.18,
+            AminoAcid::Pro => 97.12,
+            AminoAcid::Ser => 87.08,
+            AminoAcid::Thr => 101.10,
+            AminoAcid::Trp => 186.21,
+            AminoAcid::Tyr => 163.18,
+            AminoAcid::Val => 99.13,
+            AminoAcid::Stop | AminoAcid::Unknown => 0.0,
+        }
+    }
+
+    /// None for stop and unknown residues.
+    pub fn side_chain(self) -> Option<SideChain>
+    {
+        match self {
+            AminoAcid::Ala | AminoAcid::Gly | AminoAcid::Ile | AminoAcid::Leu | AminoAcid::Met
+            | AminoAcid::Phe | AminoAcid::Pro | AminoAcid::Trp | AminoAcid::Val => Some(SideChain::Nonpolar),
+            AminoAcid::Asn | AminoAcid::Cys | AminoAcid::Gln | AminoAcid::Ser | AminoAcid::Thr
+            | AminoAcid::Tyr => Some(SideChain::Polar),
+            AminoAcid::Asp | AminoAcid::Glu => Some(SideChain::Acidic),
+            AminoAcid::Arg | AminoAcid::His | AminoAcid::Lys => Some(SideChain::Basic),
+            AminoAcid::Stop | AminoAcid::Unknown => None,
+        }
+    }
+
+    /// Net side chain charge at pH 7.
+    pub fn charge(self) -> i8
+    {
+        match self.side_chain() {
+            Some(SideChain::Acidic) => -1,
+            // histidine is mostly neutral at pH 7
+            Some(SideChain::Basic) if self != AminoAcid::His => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn is_aromatic(self) -> bool
+    {
+        matches!(self, AminoAcid::Phe | AminoAcid::Trp | AminoAcid::Tyr | AminoAcid::His)
+    }
+}
+
+/// How a residue of the sample relates to the reference, from the alignment of the two proteins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Unchanged,
+    Substitution,
+    /// Aligned against a gap: the residue is not in the reference.
+    Insertion,
+    /// Outside the region covered by a local alignment, or the protein has no reference at all.
+    Unaligned,
+}
+
+/// Per-residue annotation of a drawn protein.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Residue {
+    pub amino_acid: AminoAcid,
+    /// Codon the residue was translated from.
+    pub codon: [Nucleotide; 3],
+    /// Reference residue aligned with this one, if any.
+    pub reference: Option<AminoAcid>,
+    pub change: Change,
+    /// Reference residues missing from the sample right before this residue. The last residue
+    /// also counts the ones missing after it.
+    pub deleted: usize,
+    /// Most severe effect of the nucleotide variants in the residue's codon.
+    pub effect: Option<Effect>,
+}
+
+impl Residue {
+    /// Any difference from the reference. Unknown residues are never flagged as substitutions.
+    pub fn is_mutated(&self) -> bool
+    {
+        self.change != Change::Unchanged || self.deleted > 0
+    }
+
+    /// Translated from a codon with an ambiguous base, e.g. one masked as N for its low quality score.
+    pub fn is_uncertain(&self) -> bool
+    {
+        self.codon.iter().any(|base| base.is_ambiguous())
+    }
+}
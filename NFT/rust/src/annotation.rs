@@ -0,0 +1,499 @@
+//! Feature annotation of the reference: GFF3 (https://github.com/The-Sequence-Ontology/Specifications/blob/master/gff3.md)
+//! and NCBI's five-column feature table (https://www.ncbi.nlm.nih.gov/genbank/feature_table/).
+//! Annotated CDS features define the proteins that are drawn, instead of the ORF finder.
+
+use std::ops::Range;
+
+use crate::align::Alignment;
+use crate::fasta::Record;
+use crate::frame::Strand;
+use crate::genetic_code::GeneticCode;
+use crate::nucleotide::Nucleotide;
+use crate::orf::{self, Orf};
+use crate::variant::Pairing;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feature {
+    /// ID of the record (GFF3 seqid) the feature is on.
+    pub record: String,
+    /// Feature type, e.g. "CDS" or "gene".
+    pub kind: String,
+    pub id: Option<String>,
+    /// Gene name, e.g. "ORF1ab" or "S".
+    pub name: Option<String>,
+    pub product: Option<String>,
+    pub strand: Strand,
+    /// 0-based forward-strand ranges, in reading order: descending on the reverse strand.
+    pub segments: Vec<Range<usize>>,
+}
+
+impl Feature {
+    fn new(record: &str, kind: &str, strand: Strand) -> Feature
+    {
+        Feature {
+            record: record.to_string(),
+            kind: kind.to_string(),
+            id: None,
+            name: None,
+            product: None,
+            strand,
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn is_cds(&self) -> bool
+    {
+        self.kind == "CDS"
+    }
+
+    /// Moves the start of the first segment in reading order `count` bases into it, as a GFF3 phase or a GenBank
+    /// /codon_start does: the first codon of a 5' partial CDS starts there.
+    pub fn skip_bases(&mut self, count: usize)
+    {
+        if let Some(first) = self.segments.first_mut() {
+            let count = count.min(first.len());
+            match self.strand {
+                Strand::Forward => first.start += count,
+                Strand::Reverse => first.end -= count,
+            }
+        }
+    }
+
+    // reading order; segments of a CDS may come in any order in the file
+    fn sort_segments(&mut self)
+    {
+        self.segments.sort_by_key(|s| (s.start, s.end));
+        if self.strand == Strand::Reverse {
+            self.segments.reverse();
+        }
+    }
+}
+
+fn invalid(line: usize, message: String) -> Error
+{
+    Error::InvalidAnnotation { line: line + 1, message }
+}
+
+// "%3B" and friends, as GFF3 escapes them in attribute values
+fn unescape(value: &str) -> String
+{
+    let bytes = value.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // the two bytes after '%' may start a multibyte character, so they are checked as bytes
+        if bytes[i] == b'%' && i + 3 <= bytes.len() && bytes[i + 1..i + 3].iter().all(u8::is_ascii_hexdigit) {
+            let digit = |byte: u8| (byte as char).to_digit(16).unwrap_or(0) as u8;
+            out.push(digit(bytes[i + 1]) << 4 | digit(bytes[i + 2]));
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Reads GFF3. CDS lines sharing an ID are one feature with several segments. A ##FASTA section ends the features.
+/// The phase of the first segment in reading order is applied to it: its first codon starts that many bases in.
+pub fn parse_gff3(text: &str) -> Result<Vec<Feature>>
+{
+    let mut features: Vec<Feature> = Vec::new();
+    // phase of each segment, by feature
+    let mut phases: Vec<Vec<(Range<usize>, usize)>> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns.len() != 9 {
+            return Err(invalid(i, format!("expected 9 tab-separated columns, found {}", columns.len())));
+        }
+        let start: usize = columns[3].parse().map_err(|_| invalid(i, format!("start '{}' is not a number", columns[3])))?;
+        let end: usize = columns[4].parse().map_err(|_| invalid(i, format!("end '{}' is not a number", columns[4])))?;
+        if start == 0 || end < start {
+            return Err(invalid(i, format!("{}..{} is not a valid 1-based range", start, end)));
+        }
+        let strand = if columns[6] == "-" { Strand::Reverse } else { Strand::Forward };
+        let phase = match columns[7] {
+            "0" => 0,
+            "1" => 1,
+            "2" => 2,
+            "." if columns[2] != "CDS" => 0,
+            other => return Err(invalid(i, format!("phase '{}' is not 0, 1 or 2", other))),
+        };
+
+        let mut feature = Feature::new(columns[0], columns[2], strand);
+        feature.segments.push(start - 1..end);
+        let mut gene: Option<String> = None;
+        let mut name: Option<String> = None;
+        let mut locus_tag: Option<String> = None;
+        for attribute in columns[8].split(';').filter(|a| !a.trim().is_empty()) {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), unescape(value.trim())),
+                None => continue,
+            };
+            match key {
+                "ID" => feature.id = Some(value),
+                "gene" => gene = Some(value),
+                "Name" => name = Some(value),
+                "locus_tag" => locus_tag = Some(value),
+                "product" => feature.product = Some(value),
+                _ => {}
+            }
+        }
+        feature.name = gene.or(name).or(locus_tag);
+
+        // another segment of a feature already read
+        let existing = feature.id.as_ref().and_then(|id| {
+            features.iter().position(|f| f.id.as_ref() == Some(id) && f.record == feature.record && f.kind == feature.kind)
+        });
+        match existing {
+            Some(index) => {
+                features[index].segments.push(start - 1..end);
+                phases[index].push((start - 1..end, phase));
+            }
+            None => {
+                features.push(feature);
+                phases.push(vec![(start - 1..end, phase)]);
+            }
+        }
+    }
+
+    for (feature, phases) in features.iter_mut().zip(phases) {
+        feature.sort_segments();
+        let first = match feature.segments.first() {
+            Some(first) => first,
+            None => continue,
+        };
+        let phase = phases.iter().find(|(segment, _)| segment == first).map_or(0, |(_, phase)| *phase);
+        feature.skip_bases(phase);
+    }
+    Ok(features)
+}
+
+// a coordinate of the feature table, with its partial marker ('<' or '>') dropped
+fn table_position(text: &str, line: usize) -> Result<usize>
+{
+    let digits = text.trim().trim_start_matches(['<', '>']);
+    match digits.parse() {
+        Ok(position) if position > 0 => Ok(position),
+        _ => Err(invalid(line, format!("'{}' is not a 1-based position", text.trim()))),
+    }
+}
+
+/// Reads NCBI's five-column feature table: ">Feature <seqid>" lines, then "start stop key" lines, each
+/// followed by extra "start stop" segment lines and "key value" qualifier lines. On the reverse strand start
+/// is greater than stop. As in GenBank files, a codon_start qualifier of 2 or 3 starts the first codon 1 or 2
+/// bases into the first segment.
+pub fn parse_feature_table(text: &str) -> Result<Vec<Feature>>
+{
+    let mut features: Vec<Feature> = Vec::new();
+    let mut record = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if let Some(header) = line.strip_prefix(">Feature") {
+            record = header.split_whitespace().next().unwrap_or("").to_string();
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let columns: Vec<&str> = line.split('\t').collect();
+        if columns[0].is_empty() {
+            // qualifier line: three empty columns, then key and value
+            let key = columns.get(3).map(|k| k.trim()).unwrap_or("");
+            let value = columns.get(4).map(|v| v.trim().to_string());
+            let feature = match features.last_mut() {
+                Some(feature) => feature,
+                None => return Err(invalid(i, "qualifier before any feature".to_string())),
+            };
+            match key {
+                "gene" => feature.name = value,
+                "product" => feature.product = value,
+                "protein_id" | "locus_tag" if feature.id.is_none() => feature.id = value,
+                "codon_start" => match value.as_deref() {
+                    Some("1") => {}
+                    Some("2") => feature.skip_bases(1),
+                    Some("3") => feature.skip_bases(2),
+                    other => return Err(invalid(i, format!("codon_start must be 1, 2 or 3, not '{}'", other.unwrap_or("")))),
+                },
+                _ => {}
+            }
+            continue;
+        }
+
+        if columns.len() < 2 {
+            return Err(invalid(i, "expected start and stop columns".to_string()));
+        }
+        let first = table_position(columns[0], i)?;
+        let second = table_position(columns[1], i)?;
+        let strand = if first > second { Strand::Reverse } else { Strand::Forward };
+        let segment = first.min(second) - 1..first.max(second);
+        match columns.get(2).map(|k| k.trim()).filter(|k| !k.is_empty()) {
+            Some(kind) => {
+                let mut feature = Feature::new(&record, kind, strand);
+                feature.segments.push(segment);
+                features.push(feature);
+            }
+            None => match features.last_mut() {
+                // further segment of the feature above
+                Some(feature) => feature.segments.push(segment),
+                None => return Err(invalid(i, "segment before any feature".to_string())),
+            },
+        }
+    }
+    Ok(features)
+}
+
+/// Reads a GFF3 file or a five-column feature table, whichever `text` is.
+pub fn parse(text: &str) -> Result<Vec<Feature>>
+{
+    if text.trim_start().starts_with(">Feature") {
+        parse_feature_table(text)
+    }
+    else {
+        parse_gff3(text)
+    }
+}
+
+/// Where each reference base (and the end of the reference) is in the sample, from their alignment.
+/// A deleted base maps to the sample base after the deletion; bases before or after a local alignment map to
+/// where it starts or ends in the sample.
+pub fn sample_positions(alignment: &Alignment, reference_length: usize, sample_length: usize) -> Vec<usize>
+{
+    let mut positions: Vec<usize> = Vec::with_capacity(reference_length + 1);
+    let mut next_sample = 0;
+    for column in &alignment.columns {
+        if let Some(s) = column.sample {
+            next_sample = s;
+        }
+        if let Some(r) = column.reference {
+            positions.resize(r, next_sample);
+            positions.push(next_sample);
+        }
+        if column.sample.is_some() {
+            next_sample += 1;
+        }
+    }
+    let end = if alignment.columns.is_empty() { sample_length } else { next_sample };
+    positions.resize(reference_length + 1, end);
+    positions
+}
+
+// the CDS features of each reference record: a lone unnamed record, e.g. a raw sequence, takes them all. A CDS on
+// a record that is not in the reference is an error, rather than a protein silently left out
+fn features_of<'a>(features: &'a [Feature], ref_records: &[Record]) -> Result<Vec<Vec<&'a Feature>>>
+{
+    let lone_unnamed = ref_records.len() == 1 && ref_records[0].id.is_empty();
+    let mut by_record: Vec<Vec<&Feature>> = vec![Vec::new(); ref_records.len()];
+    for feature in features.iter().filter(|f| f.is_cds()) {
+        let index = match ref_records.iter().position(|r| r.id == feature.record) {
+            Some(index) => index,
+            None if lone_unnamed => 0,
+            None => return Err(Error::UnknownRecord { record: feature.record.clone() }),
+        };
+        by_record[index].push(feature);
+    }
+    Ok(by_record)
+}
+
+// the segments of a CDS from its first whole codon in `span`, e.g. the part of the reference a read covers, and
+// whether bases were cut off its 5' end; a read that starts inside a codon would otherwise be read out of frame
+fn clip_to_codons(segments: &[Range<usize>], strand: Strand, span: &Range<usize>) -> (Vec<Range<usize>>, bool)
+{
+    // coding bases before the first one in `span`, rounded up to a whole codon
+    let mut skip = 0;
+    for segment in segments {
+        let before = match strand {
+            Strand::Forward => span.start.clamp(segment.start, segment.end) - segment.start,
+            Strand::Reverse => segment.end - span.end.clamp(segment.start, segment.end),
+        };
+        skip += before;
+        if before < segment.len() {
+            break;
+        }
+    }
+    let skip = skip.div_ceil(3) * 3;
+
+    let mut left = skip;
+    let mut clipped: Vec<Range<usize>> = Vec::new();
+    for segment in segments {
+        let cut = left.min(segment.len());
+        left -= cut;
+        let rest = match strand {
+            Strand::Forward => segment.start + cut..segment.end,
+            Strand::Reverse => segment.start..segment.end - cut,
+        };
+        if !rest.is_empty() {
+            clipped.push(rest);
+        }
+    }
+    (clipped, skip > 0)
+}
+
+fn bases(record: &Record) -> Vec<Nucleotide>
+{
+    record.sequence.chars().map(|c| Nucleotide::from_char(c).unwrap_or(Nucleotide::N)).collect()
+}
+
+/// The annotated CDS of each reference record as ORFs, and the same CDS carried over to each sample record
+/// paired with it through their alignment (see variant::align_records); a read only gets the CDS it overlaps, from
+/// the first whole codon it covers, and on the opposite strand if the read is from the reverse one.
+/// Returns the sample ORFs, then the reference ORFs, or an error if a CDS is on a record the reference does not have.
+pub fn annotated_orfs(records: &[Record], ref_records: &[Record], pairings: &[Pairing], features: &[Feature], code: &GeneticCode) -> Result<(Vec<Orf>, Vec<Orf>)>
+{
+    let features = features_of(features, ref_records)?;
+    let reference_bases: Vec<Vec<Nucleotide>> = ref_records.iter().map(bases).collect();
+    let mut ref_enc: Vec<Orf> = Vec::new();
+    for ((reference, bases), features) in ref_records.iter().zip(&reference_bases).zip(&features) {
+        for feature in features {
+            ref_enc.push(orf::from_segments(&reference.id, bases, feature.segments.clone(), feature.strand, true, code, feature.name.clone()));
+        }
+    }
+
+    let mut enc: Vec<Orf> = Vec::new();
+    for pairing in pairings {
+        let record = &records[pairing.sample];
+        let sample_bases = bases(record);
+        // the CDS is carried over to the record as it was aligned, then back to its forward strand
+        let strand = |strand: Strand| match (pairing.strand, strand) {
+            (Strand::Forward, strand) => strand,
+            (Strand::Reverse, Strand::Forward) => Strand::Reverse,
+            (Strand::Reverse, Strand::Reverse) => Strand::Forward,
+        };
+        let reference_length = reference_bases[pairing.reference].len();
+        let positions = sample_positions(&pairing.alignment, reference_length, sample_bases.len());
+        let lift = |position: usize| positions[position.min(reference_length)];
+        let span = pairing.reference_span();
+
+        for feature in &features[pairing.reference] {
+            if !feature.segments.iter().any(|s| s.start < span.end && span.start < s.end) {
+                continue;
+            }
+            let (segments, truncated) = clip_to_codons(&feature.segments, feature.strand, &span);
+            if segments.is_empty() {
+                continue;
+            }
+            let lifted: Vec<Range<usize>> = segments.iter().map(|s| pairing.on_sample(lift(s.start)..lift(s.end), sample_bases.len())).collect();
+            enc.push(orf::from_segments(&record.id, &sample_bases, lifted, strand(feature.strand), !truncated, code, feature.name.clone()));
+        }
+    }
+    Ok((enc, ref_enc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amino_acid::AminoAcid;
+    use crate::genetic_code::STANDARD;
+    use crate::testing::{genome, record};
+    use crate::variant;
+
+    #[test]
+    fn gff3_segments_in_reading_order()
+    {
+        let features = parse_gff3("##gff-version 3
+chr\t.\tgene\t1\t90\t.\t-\t.\tID=gene-1;Name=g
+chr\t.\tCDS\t61\t90\t.\t-\t0\tID=cds-1;gene=g;product=a%3Bb
+chr\t.\tCDS\t1\t30\t.\t-\t0\tID=cds-1;gene=g
+chr\t.\tCDS\t101\t200\t.\t+\t0\tID=cds-2;locus_tag=L2
+").unwrap();
+        assert_eq!(features.len(), 3);
+        assert_eq!((features[1].name.as_deref(), features[1].product.as_deref()), (Some("g"), Some("a;b")));
+        assert_eq!(features[1].segments, vec![60..90, 0..30]);
+        assert_eq!((features[2].name.as_deref(), features[2].strand), (Some("L2"), Strand::Forward));
+    }
+
+    #[test]
+    fn gff3_phase_of_the_first_segment()
+    {
+        // the first codon starts 1 base into 11..40 on the forward strand, 2 bases into 61..90 on the reverse one
+        let features = parse_gff3("chr\t.\tCDS\t51\t60\t.\t+\t0\tID=a
+chr\t.\tCDS\t11\t40\t.\t+\t1\tID=a
+chr\t.\tCDS\t61\t90\t.\t-\t2\tID=b
+chr\t.\tCDS\t1\t30\t.\t-\t1\tID=b
+").unwrap();
+        assert_eq!(features[0].segments, vec![11..40, 50..60]);
+        assert_eq!(features[1].segments, vec![60..88, 0..30]);
+        assert!(matches!(parse_gff3("chr\t.\tCDS\t1\t30\t.\t+\t.\tID=a\n"), Err(Error::InvalidAnnotation { line: 1, .. })));
+    }
+
+    #[test]
+    fn feature_table()
+    {
+        let features = parse_feature_table(">Feature chr
+90\t61\tCDS
+30\t1
+\t\t\tgene\tg
+\t\t\tprotein_id\tP1
+101\t>200\tCDS
+").unwrap();
+        assert_eq!((features[0].record.as_str(), features[0].strand), ("chr", Strand::Reverse));
+        assert_eq!((features[0].name.as_deref(), features[0].id.as_deref()), (Some("g"), Some("P1")));
+        assert_eq!(features[0].segments, vec![60..90, 0..30]);
+        assert_eq!(features[1].segments, vec![100..200]);
+    }
+
+    #[test]
+    fn feature_table_codon_start()
+    {
+        let features = parse_feature_table(">Feature chr
+<90\t61\tCDS
+30\t1
+\t\t\tcodon_start\t3
+<101\t200\tCDS
+\t\t\tcodon_start\t2
+").unwrap();
+        assert_eq!(features[0].segments, vec![60..88, 0..30]);
+        assert_eq!(features[1].segments, vec![101..200]);
+        assert!(matches!(parse_feature_table(">Feature chr\n1\t90\tCDS\n\t\t\tcodon_start\t4\n"), Err(Error::InvalidAnnotation { line: 3, .. })));
+    }
+
+    #[test]
+    fn escapes_in_attributes()
+    {
+        assert_eq!(unescape("a%3Bb%2c"), "a;b,");
+        // not escapes: a sign, a non-ASCII character, a cut-off one
+        assert_eq!(unescape("%+1%a\u{20ac}%4"), "%+1%a\u{20ac}%4");
+        let features = parse_gff3("chr\t.\tCDS\t1\t30\t.\t+\t0\tID=a;gene=%a\u{20ac}\n").unwrap();
+        assert_eq!(features[0].name.as_deref(), Some("%a\u{20ac}"));
+    }
+
+    #[test]
+    fn reads_starting_inside_a_cds()
+    {
+        // 500 bases, then Met, 60 codons with no stop and TAA, then 500 bases
+        let codons: String = genome(180, 7).as_bytes().chunks(3)
+            .map(|c| match c { b"TAA" | b"TAG" | b"TGA" => "TAC".to_string(), c => String::from_utf8(c.to_vec()).unwrap() })
+            .collect();
+        let reference = format!("{}ATG{}TAA{}", genome(500, 5), codons, genome(500, 6));
+        let cds = 500..686;
+        let mut feature = Feature::new("chr", "CDS", Strand::Forward);
+        feature.segments.push(cds.clone());
+
+        for into in [0, 1, 2, 3] {
+            let read = &reference[cds.start + into..cds.end + 100];
+            // the same read from the reverse strand
+            let records = [record("read", read), record("reverse", &crate::nucleotide::reverse_complement(read))];
+            let references = [record("chr", &reference)];
+            let pairings = variant::align_records(&records, &references);
+            let (enc, ref_enc) = annotated_orfs(&records, &references, &pairings, std::slice::from_ref(&feature), &STANDARD).unwrap();
+            // a read that starts inside the first codon is read from the second one, and that one is not made Met
+            let skipped = if into == 0 { 0 } else { 1 };
+            let coding = 3 * skipped - into..cds.len() - into;
+            assert_ne!(ref_enc[0].protein[1], AminoAcid::Met);
+            assert_eq!(enc[0].protein, ref_enc[0].protein[skipped..], "{} bases in", into);
+            assert_eq!(enc[0].segments, vec![coding.clone()], "{} bases in", into);
+            assert_eq!((enc[1].protein.as_slice(), enc[1].strand()), (&ref_enc[0].protein[skipped..], Strand::Reverse), "{} bases in", into);
+            assert_eq!(enc[1].segments, vec![read.len() - coding.end..read.len() - coding.start], "{} bases in", into);
+        }
+    }
+}
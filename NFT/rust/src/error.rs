@@ -0,0 +1,127 @@
+//! Crate error type. On the wasm side errors are thrown as JS `Error` objects that carry
+//! the variant name in `kind` plus every field of the variant (`position`, `record`, ...).
+
+use std::fmt;
+
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind")]
+pub enum Error {
+    /// The sample or reference input holds no bases at all.
+    EmptySequence { input: String },
+    /// A character that is neither a nucleotide (IUPAC) nor whitespace.
+    /// `position` is the character offset inside the record's sequence.
+    InvalidCharacter { record: String, position: usize, character: char },
+    /// A coding sequence whose length is not a multiple of three; `position` is the first leftover base.
+    PartialCodon { record: String, position: usize },
+    /// No start codon followed by a stop codon anywhere in the input.
+    NoOpenReadingFrame { input: String },
+    /// A strip needs at least two pairs of points to form a quad.
+    TooFewPoints { count: usize },
+    /// The options JSON could not be read.
+    InvalidOptions { message: String },
+    /// Not one of the NCBI translation table numbers.
+    UnknownGeneticCode { id: u8 },
+    /// A VCF line that cannot be read; `line` counts from 1.
+    InvalidVcf { line: usize, message: String },
+    /// A VCF CHROM that names none of the reference records.
+    UnknownContig { contig: String },
+    /// The REF allele of a VCF record differs from the reference at its 1-based `position`.
+    ReferenceMismatch { record: String, position: usize, expected: String, found: String },
+    /// Two VCF records change the same reference bases; positions are 1-based.
+    OverlappingVariants { record: String, position: usize, previous: usize },
+    /// A GFF3 or feature-table line that cannot be read; `line` counts from 1.
+    InvalidAnnotation { line: usize, message: String },
+    /// A GenBank line that cannot be read; `line` counts from 1.
+    InvalidGenBank { line: usize, message: String },
+    /// A FASTQ line that cannot be read; `line` counts from 1.
+    InvalidFastq { line: usize, message: String },
+    /// Bytes that are neither text nor gzip-compressed text.
+    InvalidInput { message: String },
+    /// A .2bit file that cannot be read.
+    InvalidTwoBit { message: String },
+    /// No record of the input has this name.
+    UnknownRecord { record: String },
+    /// A region option that cannot be read, or that holds no protein.
+    InvalidRegion { region: String, message: String },
+    /// A region option that names no CDS of the annotation.
+    UnknownGene { gene: String },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Attaches the FASTA record ID to errors that point inside a sequence.
+    pub fn in_record(self, id: &str) -> Error
+    {
+        match self {
+            Error::InvalidCharacter { position, character, .. } => Error::InvalidCharacter { record: id.to_string(), position, character },
+            Error::PartialCodon { position, .. } => Error::PartialCodon { record: id.to_string(), position },
+            other => other,
+        }
+    }
+}
+
+fn record_prefix(record: &str) -> String
+{
+    if record.is_empty() {
+        String::new()
+    }
+    else {
+        format!("record '{}': ", record)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            Error::EmptySequence { input } => write!(f, "{} sequence is empty", input),
+            Error::InvalidCharacter { record, position, character } =>
+                write!(f, "{}invalid character {:?} at position {}", record_prefix(record), character, position),
+            Error::PartialCodon { record, position } =>
+                write!(f, "{}partial codon at position {}: length is not a multiple of three", record_prefix(record), position),
+            Error::NoOpenReadingFrame { input } =>
+                write!(f, "{} sequence has no start codon followed by a stop codon", input),
+            Error::TooFewPoints { count } => write!(f, "strip needs at least 4 points, got {}", count),
+            Error::InvalidOptions { message } => write!(f, "invalid options: {}", message),
+            Error::UnknownGeneticCode { id } => write!(f, "unknown genetic code table {}", id),
+            Error::InvalidVcf { line, message } => write!(f, "VCF line {}: {}", line, message),
+            Error::UnknownContig { contig } => write!(f, "VCF contig '{}' is not in the reference", contig),
+            Error::ReferenceMismatch { record, position, expected, found } =>
+                write!(f, "{}VCF REF {} at position {} does not match the reference, which has {}", record_prefix(record), expected, position, found),
+            Error::OverlappingVariants { record, position, previous } =>
+                write!(f, "{}VCF records at positions {} and {} overlap", record_prefix(record), previous, position),
+            Error::InvalidAnnotation { line, message } => write!(f, "annotation line {}: {}", line, message),
+            Error::InvalidGenBank { line, message } => write!(f, "GenBank line {}: {}", line, message),
+            Error::InvalidFastq { line, message } => write!(f, "FASTQ line {}: {}", line, message),
+            Error::InvalidInput { message } => write!(f, "unreadable input: {}", message),
+            Error::InvalidTwoBit { message } => write!(f, "invalid .2bit file: {}", message),
+            Error::UnknownRecord { record } => write!(f, "no record named '{}'", record),
+            Error::InvalidRegion { region, message } => write!(f, "region '{}': {}", region, message),
+            Error::UnknownGene { gene } => write!(f, "no annotated gene named '{}'", gene),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for JsValue {
+    fn from(error: Error) -> JsValue
+    {
+        let js_error = js_sys::Error::new(&error.to_string());
+        if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(&error) {
+            for (key, value) in fields {
+                let value = match value {
+                    serde_json::Value::String(s) => JsValue::from_str(&s),
+                    serde_json::Value::Number(n) => JsValue::from_f64(n.as_f64().unwrap_or(f64::NAN)),
+                    other => JsValue::from_str(&other.to_string()),
+                };
+                let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str(&key), &value);
+            }
+        }
+        js_error.into()
+    }
+}
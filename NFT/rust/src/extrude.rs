@@ -0,0 +1,287 @@
+//! Extruded strips: a cross-section swept along the path of a strip, so it has a thickness, and caps closing it
+//! where it ends. The flat ribbon the strips have always been is one of the cross-sections.
+
+use serde::Deserialize;
+
+use crate::amino_acid::{AminoAcid, SideChain};
+use crate::transport;
+use crate::Vertex;
+
+fn one() -> f32
+{
+    1.0
+}
+
+fn default_aspect() -> f32
+{
+    0.3
+}
+
+fn default_sides() -> usize
+{
+    12
+}
+
+/// Shape swept along a strip. Sizes are relative to the width of the ribbon at that point, so a shape tapers
+/// along with the protein.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum CrossSection {
+    /// Flat, `scale` times as wide as the ribbon.
+    Ribbon {
+        #[serde(default = "one")]
+        scale: f32,
+    },
+    /// A tube, `scale` times as wide as the ribbon, with `sides` faces.
+    Circle {
+        #[serde(default = "one")]
+        scale: f32,
+        #[serde(default = "default_sides")]
+        sides: usize,
+    },
+    /// `scale` times as wide as the ribbon and `aspect` times as thick as wide.
+    Ellipse {
+        #[serde(default = "one")]
+        scale: f32,
+        #[serde(default = "default_aspect")]
+        aspect: f32,
+        #[serde(default = "default_sides")]
+        sides: usize,
+    },
+    /// A flat box, `scale` times as wide as the ribbon and `aspect` times as thick as wide.
+    Rectangle {
+        #[serde(default = "one")]
+        scale: f32,
+        #[serde(default = "default_aspect")]
+        aspect: f32,
+    },
+}
+
+impl Default for CrossSection {
+    fn default() -> CrossSection
+    {
+        CrossSection::Ribbon { scale: 1.0 }
+    }
+}
+
+/// Cross-section of the residues of each side chain class, e.g. tubes for the nonpolar residues and broad ribbons
+/// for the polar ones. Stop and unknown residues take `other`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CrossSections {
+    pub nonpolar: CrossSection,
+    pub polar: CrossSection,
+    pub acidic: CrossSection,
+    pub basic: CrossSection,
+    pub other: CrossSection,
+}
+
+impl CrossSections {
+    pub fn of(&self, amino_acid: AminoAcid) -> &CrossSection
+    {
+        match amino_acid.side_chain() {
+            Some(SideChain::Nonpolar) => &self.nonpolar,
+            Some(SideChain::Polar) => &self.polar,
+            Some(SideChain::Acidic) => &self.acidic,
+            Some(SideChain::Basic) => &self.basic,
+            None => &self.other,
+        }
+    }
+}
+
+// one face of a cross-section, from a point to the next, in units of the half-width (x, across the strip) and of
+// the half-thickness (y, along the strip normal), with the surface normal at both ends in the same axes
+struct Side {
+    from: [f32; 2],
+    to: [f32; 2],
+    from_normal: [f32; 2],
+    to_normal: [f32; 2],
+}
+
+impl CrossSection {
+    fn scale(&self) -> f32
+    {
+        match *self {
+            CrossSection::Ribbon { scale } | CrossSection::Circle { scale, .. }
+            | CrossSection::Ellipse { scale, .. } | CrossSection::Rectangle { scale, .. } => scale,
+        }
+    }
+
+    // thickness over width
+    fn aspect(&self) -> f32
+    {
+        match *self {
+            CrossSection::Ribbon { .. } => 0.0,
+            CrossSection::Circle { .. } => 1.0,
+            CrossSection::Ellipse { aspect, .. } | CrossSection::Rectangle { aspect, .. } => aspect,
+        }
+    }
+
+    /// Flat, with no faces to close.
+    pub fn is_flat(&self) -> bool
+    {
+        matches!(self, CrossSection::Ribbon { .. })
+    }
+
+    // the faces, going around the shape
+    fn sides(&self) -> Vec<Side>
+    {
+        match *self {
+            CrossSection::Ribbon { .. } => vec![Side { from: [1.0, 0.0], to: [-1.0, 0.0], from_normal: [0.0, 1.0], to_normal: [0.0, 1.0] }],
+            CrossSection::Circle { sides, .. } | CrossSection::Ellipse { sides, .. } => {
+                let aspect = self.aspect();
+                let sides = sides.max(3);
+                let point = |k: usize| {
+                    let angle = 2.0 * std::f32::consts::PI * (k % sides) as f32 / sides as f32;
+                    let (sin, cos) = angle.sin_cos();
+                    // the normal of an ellipse of half-axes 1 and aspect at (cos, sin aspect)
+                    ([cos, sin], [cos, sin / aspect.max(1e-3)])
+                };
+                (0..sides)
+                    .map(|k| {
+                        let ((from, from_normal), (to, to_normal)) = (point(k), point(k + 1));
+                        Side { from, to, from_normal, to_normal }
+                    })
+                    .collect()
+            }
+            CrossSection::Rectangle { .. } => {
+                let corners = [[1.0, 1.0], [-1.0, 1.0], [-1.0, -1.0], [1.0, -1.0]];
+                let normals = [[0.0, 1.0], [-1.0, 0.0], [0.0, -1.0], [1.0, 0.0]];
+                (0..4)
+                    .map(|k| Side { from: corners[k], to: corners[(k + 1) % 4], from_normal: normals[k], to_normal: normals[k] })
+                    .collect()
+            }
+        }
+    }
+}
+
+// center, half-width and half-thickness vectors, and the unit axes of the normals, of the row i of a strip
+fn row_frame(section: &CrossSection, points: &[glm::Vec3], normals: &[glm::Vec3], i: usize) -> [glm::Vec3; 5]
+{
+    let (top, bottom) = (points[2 * i], points[2 * i + 1]);
+    let across = 0.5 * section.scale() * (top - bottom);
+    let y = normals[2 * i];
+    let x = transport::safe_normalize(&(top - bottom), &transport::perpendicular(&y));
+    let thickness = glm::length(&across) * section.aspect() * y;
+    [0.5 * (top + bottom), across, thickness, x, y]
+}
+
+/// The faces of `section` swept along the rows of a strip (top and bottom point of each row, with their normals
+/// and colors, as strip_to_vertices takes them): the rows of one strip per face. A plain ribbon is the strip.
+pub fn sweep(section: &CrossSection, points: &[glm::Vec3], normals: &[glm::Vec3], colors: &[glm::Vec3]) -> Vec<(Vec<glm::Vec3>, Vec<glm::Vec3>, Vec<glm::Vec3>)>
+{
+    if *section == CrossSection::default() {
+        return vec![(points.to_vec(), normals.to_vec(), colors.to_vec())];
+    }
+    let frames: Vec<[glm::Vec3; 5]> = (0..points.len() / 2).map(|i| row_frame(section, points, normals, i)).collect();
+    section.sides().iter()
+        .map(|side| {
+            let mut face = (Vec::new(), Vec::new(), Vec::new());
+            for (i, [center, across, thickness, x, y]) in frames.iter().enumerate() {
+                for (point, normal) in [(side.from, side.from_normal), (side.to, side.to_normal)] {
+                    face.0.push(center + point[0] * across + point[1] * thickness);
+                    face.1.push(transport::safe_normalize(&(normal[0] * x + normal[1] * y), y));
+                    face.2.push(colors[2 * i]);
+                }
+            }
+            face
+        })
+        .collect()
+}
+
+/// The cap closing `section` at the row i of a strip: its center and the points around it, counter-clockwise seen
+/// from the side it faces, along the path or back at the start of a strip. None for a ribbon, which has nothing
+/// to close.
+pub fn cap(section: &CrossSection, points: &[glm::Vec3], normals: &[glm::Vec3], colors: &[glm::Vec3], i: usize, at_end: bool) -> Option<(Vertex, Vec<Vertex>)>
+{
+    if section.is_flat() || 2 * i + 1 >= points.len() {
+        return None;
+    }
+    let [center, across, thickness, x, y] = row_frame(section, points, normals, i);
+    // rows are built with normal = direction x (top - bottom), so the path goes along (top - bottom) x normal
+    let forward = transport::safe_normalize(&glm::cross(&x, &y), &transport::perpendicular(&y));
+    let normal = if at_end { forward } else { -forward };
+    let color = colors[2 * i];
+    // the sides go counter-clockwise around `forward`
+    let mut rim: Vec<Vertex> = section.sides().iter()
+        .map(|side| Vertex { position: center + side.from[0] * across + side.from[1] * thickness, normal, color })
+        .collect();
+    if !at_end {
+        rim.reverse();
+    }
+    Some((Vertex { position: center, normal, color }, rim))
+}
+
+/// A fan of triangles around `center`, closing the loop of `rim`, as single triangles.
+pub fn fan_to_vertices(center: Vertex, rim: &[Vertex]) -> Vec<Vertex>
+{
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for i in 0..rim.len() {
+        vertices.push(center);
+        vertices.push(rim[i]);
+        vertices.push(rim[(i + 1) % rim.len()]);
+    }
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a strip of 4 rows along +x, one unit wide, with its normal up: the path goes along (top - bottom) x normal
+    fn strip() -> (Vec<glm::Vec3>, Vec<glm::Vec3>, Vec<glm::Vec3>)
+    {
+        let points: Vec<glm::Vec3> = (0..4).flat_map(|i| [glm::vec3(i as f32, 0.5, 0.0), glm::vec3(i as f32, -0.5, 0.0)]).collect();
+        (points, vec![glm::vec3(0.0, 0.0, 1.0); 8], vec![glm::vec3(1.0, 0.0, 0.0); 8])
+    }
+
+    fn sections() -> [CrossSection; 5]
+    {
+        [
+            CrossSection::Ribbon { scale: 1.0 },
+            CrossSection::Circle { scale: 1.0, sides: 12 },
+            CrossSection::Circle { scale: 1.0, sides: 1 },
+            CrossSection::Ellipse { scale: 2.0, aspect: 0.3, sides: 8 },
+            CrossSection::Rectangle { scale: 1.0, aspect: 0.3 },
+        ]
+    }
+
+    #[test]
+    fn faces_go_around_the_path()
+    {
+        let (points, normals, colors) = strip();
+        let faces: Vec<usize> = sections().iter().map(|section| sweep(section, &points, &normals, &colors).len()).collect();
+        // a circle has at least 3 sides
+        assert_eq!(faces, vec![1, 12, 3, 8, 4]);
+
+        for section in &sections() {
+            for (face_points, face_normals, _) in sweep(section, &points, &normals, &colors) {
+                assert_eq!((face_points.len(), face_normals.len()), (8, 8));
+                for normal in &face_normals {
+                    assert!((glm::length(normal) - 1.0).abs() < 1e-5, "{:?}", section);
+                    assert!(normal.x.abs() < 1e-5, "{:?}: {:?} is not across the path", section, normal);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn caps_face_out_of_the_strip()
+    {
+        let (points, normals, colors) = strip();
+        assert!(cap(&CrossSection::default(), &points, &normals, &colors, 0, false).is_none());
+        for section in &sections()[1..] {
+            for (i, at_end) in [(0, false), (3, true)] {
+                let (center, rim) = cap(section, &points, &normals, &colors, i, at_end).unwrap();
+                let outward = if at_end { 1.0 } else { -1.0 };
+                assert_eq!(center.normal, glm::vec3(outward, 0.0, 0.0));
+                assert_eq!(rim.len(), sweep(section, &points, &normals, &colors).len());
+                // every triangle of the fan is wound counter-clockwise around the normal
+                for k in 0..rim.len() {
+                    let (a, b) = (rim[k].position - center.position, rim[(k + 1) % rim.len()].position - center.position);
+                    assert!(glm::dot(&glm::cross(&a, &b), &center.normal) > 0.0, "{:?} at_end {}", section, at_end);
+                }
+            }
+        }
+    }
+}
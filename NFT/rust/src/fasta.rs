@@ -0,0 +1,119 @@
+//! FASTA / multi-FASTA reader and writer.
+//! Plain sequences without any '>' header are accepted too, and come back as a single unnamed record.
+
+use serde::Serialize;
+
+use crate::Result;
+
+/// One named sequence of a (multi-)FASTA file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    /// First word of the header line, e.g. "MN908947.3". Empty for raw sequences.
+    pub id: String,
+    /// Rest of the header line.
+    pub description: String,
+    /// Bases only, line breaks and whitespace removed.
+    pub sequence: String,
+}
+
+impl Record {
+    fn new(header: &str) -> Record
+    {
+        let header = header.trim();
+        let (id, description) = match header.find(char::is_whitespace) {
+            Some(pos) => (&header[..pos], header[pos..].trim_start()),
+            None => (header, ""),
+        };
+        Record {
+            id: id.to_string(),
+            description: description.to_string(),
+            sequence: String::new(),
+        }
+    }
+}
+
+/// True if the text starts with a FASTA header line.
+pub fn is_fasta(text: &str) -> bool
+{
+    text.trim_start().starts_with('>')
+}
+
+/// Splits a FASTA, multi-FASTA or raw sequence text into records.
+pub fn parse(text: &str) -> Vec<Record>
+{
+    // text lines cannot fail to read
+    read_lines(text.lines().map(Ok)).unwrap_or_default()
+}
+
+/// Same as parse, one line at a time, e.g. straight out of a decompressor; stops at the first line that
+/// cannot be read.
+pub fn read_lines<I, S>(lines: I) -> Result<Vec<Record>>
+where
+    I: Iterator<Item = Result<S>>,
+    S: AsRef<str>,
+{
+    let mut records: Vec<Record> = Vec::new();
+    let mut current: Option<Record> = None;
+
+    for line in lines {
+        let line = line?;
+        let line = line.as_ref().trim_end_matches('\r');
+        if let Some(header) = line.strip_prefix('>') {
+            if let Some(record) = current.take() {
+                records.push(record);
+            }
+            current = Some(Record::new(header));
+        }
+        else if line.starts_with(';') {
+            // old-style comment line
+            continue;
+        }
+        else {
+            let bases = line.chars().filter(|c| !c.is_whitespace());
+            match current.as_mut() {
+                Some(record) => record.sequence.extend(bases),
+                None => {
+                    // sequence data before any header: raw, unnamed sequence
+                    let mut record = Record::new("");
+                    record.sequence.extend(bases);
+                    if !record.sequence.is_empty() {
+                        current = Some(record);
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(record) = current {
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Writes records as FASTA, 60 bases per line. A lone unnamed record is written as a raw sequence.
+pub fn write(records: &[Record]) -> String
+{
+    if let [record] = records {
+        if record.id.is_empty() && record.description.is_empty() {
+            return record.sequence.clone();
+        }
+    }
+
+    let mut text = String::new();
+    for record in records {
+        text.push('>');
+        text.push_str(&record.id);
+        if !record.description.is_empty() {
+            text.push(' ');
+            text.push_str(&record.description);
+        }
+        text.push('\n');
+        let bases: Vec<char> = record.sequence.chars().collect();
+        for line in bases.chunks(60) {
+            text.extend(line);
+            text.push('\n');
+        }
+    }
+    text
+}
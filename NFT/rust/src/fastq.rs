@@ -0,0 +1,156 @@
+//! FASTQ reader: reads with a Phred quality per base (Sanger / Illumina 1.8+ encoding, offset 33).
+
+use crate::fasta::Record;
+use crate::{Error, Result};
+
+// quality character of Phred score 0
+const PHRED_OFFSET: u8 = b'!';
+
+/// One read: its sequence as a record, and the Phred score of each base.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FastqRecord {
+    pub record: Record,
+    pub quality: Vec<u8>,
+}
+
+/// `sequence` with every base scored below `min_quality` replaced by N.
+pub fn mask(sequence: &str, quality: &[u8], min_quality: u8) -> String
+{
+    sequence.chars().zip(quality)
+        .map(|(base, &q)| if q < min_quality { 'N' } else { base })
+        .collect()
+}
+
+/// True if the text starts with a FASTQ header line.
+pub fn is_fastq(text: &str) -> bool
+{
+    text.trim_start().starts_with('@')
+}
+
+fn invalid(line: usize, message: String) -> Error
+{
+    Error::InvalidFastq { line: line + 1, message }
+}
+
+/// Reads every record of a FASTQ file. Sequence and quality may be wrapped over several lines.
+pub fn parse(text: &str) -> Result<Vec<FastqRecord>>
+{
+    read_lines(text.lines().map(Ok))
+}
+
+/// Same as parse, one line at a time, e.g. straight out of a decompressor.
+pub fn read_lines<I, S>(lines: I) -> Result<Vec<FastqRecord>>
+where
+    I: Iterator<Item = Result<S>>,
+    S: AsRef<str>,
+{
+    let mut records: Vec<FastqRecord> = Vec::new();
+    let mut lines = lines.enumerate();
+    while let Some((i, line)) = lines.next() {
+        let line = line?;
+        let line = line.as_ref().trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let header = match line.strip_prefix('@') {
+            Some(header) => header,
+            None => return Err(invalid(i, "expected a header line starting with '@'".to_string())),
+        };
+        let header = header.trim();
+        let (id, description) = match header.find(char::is_whitespace) {
+            Some(pos) => (&header[..pos], header[pos..].trim_start()),
+            None => (header, ""),
+        };
+
+        let mut sequence = String::new();
+        let mut separator = None;
+        for (j, line) in lines.by_ref() {
+            let line = line?;
+            let line = line.as_ref();
+            if line.starts_with('+') {
+                separator = Some(j);
+                break;
+            }
+            sequence.extend(line.chars().filter(|c| !c.is_whitespace()));
+        }
+        let separator = match separator {
+            Some(separator) => separator,
+            None => return Err(invalid(i, format!("read '{}' has no '+' line", id))),
+        };
+
+        // the quality string is as long as the sequence; '@' may start a quality line, so count, don't look
+        let mut quality: Vec<u8> = Vec::with_capacity(sequence.len());
+        while quality.len() < sequence.len() {
+            let (j, line) = match lines.next() {
+                Some((j, line)) => (j, line?),
+                None => return Err(invalid(separator, format!("read '{}' is missing quality scores", id))),
+            };
+            for byte in line.as_ref().bytes().filter(|b| !b.is_ascii_whitespace()) {
+                if !(PHRED_OFFSET..=b'~').contains(&byte) {
+                    return Err(invalid(j, format!("'{}' is not a quality score", byte as char)));
+                }
+                quality.push(byte - PHRED_OFFSET);
+            }
+        }
+        if quality.len() != sequence.len() {
+            return Err(invalid(separator, format!("read '{}' has {} bases but {} quality scores", id, sequence.len(), quality.len())));
+        }
+
+        records.push(FastqRecord {
+            record: Record { id: id.to_string(), description: description.to_string(), sequence },
+            quality,
+        });
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+
+    // two reads, the second one wrapped, with a quality line that starts with '@' (Phred 31)
+    const READS: &str = "@r1 first read
+ACGT
++
+!5?I
+@r2
+ACG
+TA
++r2
+@@+
+!!
+";
+
+    #[test]
+    fn masked_below_the_threshold()
+    {
+        let reads = parse(READS).unwrap();
+        assert_eq!((reads[0].record.id.as_str(), reads[0].record.description.as_str()), ("r1", "first read"));
+        assert_eq!(reads[0].quality, vec![0, 20, 30, 40]);
+        assert_eq!((reads[1].record.sequence.as_str(), reads[1].quality.as_slice()), ("ACGTA", &[31, 31, 10, 0, 0][..]));
+        // a base scored exactly the threshold is kept
+        assert_eq!(mask("ACGT", &reads[0].quality, 20), "NCGT");
+        assert_eq!(mask("ACGT", &reads[0].quality, 21), "NNGT");
+        assert_eq!(mask("ACGT", &reads[0].quality, 0), "ACGT");
+
+        assert!(matches!(parse("@r\nACGT\n+\n!!!\n"), Err(Error::InvalidFastq { line: 3, .. })));
+        assert!(matches!(parse("@r\nACGT\n+\n!! !\x7f\n"), Err(Error::InvalidFastq { line: 4, .. })));
+    }
+
+    #[test]
+    fn gzip_fastq()
+    {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(READS.as_bytes()).unwrap();
+        let bytes = encoder.finish().unwrap();
+        assert_eq!(input::detect_compression(&bytes), input::Compression::Gzip);
+
+        let mut reads = input::parse_bytes(&bytes).unwrap();
+        assert_eq!(reads.qualities, vec![vec![0, 20, 30, 40], vec![31, 31, 10, 0, 0]]);
+        reads.mask(20);
+        let sequences: Vec<&str> = reads.records.iter().map(|r| r.sequence.as_str()).collect();
+        assert_eq!(sequences, vec!["NCGT", "ACNNN"]);
+    }
+}
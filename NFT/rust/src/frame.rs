@@ -0,0 +1,123 @@
+//! Reading frames: three codon phases on each of the two strands.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nucleotide;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// Serialized as its label, e.g. "-2".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub strand: Strand,
+    /// Bases skipped from the start of the strand: 0, 1 or 2.
+    pub offset: u8,
+}
+
+impl Frame {
+    pub const FORWARD_1: Frame = Frame { strand: Strand::Forward, offset: 0 };
+
+    /// +1, +2, +3, -1, -2, -3
+    pub const ALL: [Frame; 6] = [
+        Frame { strand: Strand::Forward, offset: 0 },
+        Frame { strand: Strand::Forward, offset: 1 },
+        Frame { strand: Strand::Forward, offset: 2 },
+        Frame { strand: Strand::Reverse, offset: 0 },
+        Frame { strand: Strand::Reverse, offset: 1 },
+        Frame { strand: Strand::Reverse, offset: 2 },
+    ];
+
+    /// Position in Frame::ALL.
+    pub fn index(self) -> usize
+    {
+        match self.strand {
+            Strand::Forward => self.offset as usize,
+            Strand::Reverse => 3 + self.offset as usize,
+        }
+    }
+
+    /// Parses the usual "+1" .. "-3" notation.
+    pub fn from_label(label: &str) -> Option<Frame>
+    {
+        let label = label.trim();
+        let strand = match label.chars().next()? {
+            '+' => Strand::Forward,
+            '-' => Strand::Reverse,
+            _ => return None,
+        };
+        match &label[1..] {
+            "1" => Some(Frame { strand, offset: 0 }),
+            "2" => Some(Frame { strand, offset: 1 }),
+            "3" => Some(Frame { strand, offset: 2 }),
+            _ => None,
+        }
+    }
+
+    /// The bases read in this frame: the sequence itself or its reverse complement, with the first `offset` bases skipped.
+    pub fn bases(self, seq: &str) -> String
+    {
+        match self.strand {
+            Strand::Forward => seq.chars().skip(self.offset as usize).collect(),
+            Strand::Reverse => nucleotide::reverse_complement(seq).chars().skip(self.offset as usize).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let sign = match self.strand {
+            Strand::Forward => '+',
+            Strand::Reverse => '-',
+        };
+        write!(f, "{}{}", sign, self.offset + 1)
+    }
+}
+
+impl Serialize for Frame {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Which frames get drawn. In options JSON: "+1" .. "-3", "all" or "best".
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum FrameSelection {
+    /// One frame only.
+    Single(Frame),
+    /// All six frames, each on its own ring.
+    All,
+    /// Per record, the frame whose open reading frames hold the most residues.
+    Best,
+}
+
+impl Default for FrameSelection {
+    fn default() -> FrameSelection
+    {
+        FrameSelection::Single(Frame::FORWARD_1)
+    }
+}
+
+impl TryFrom<String> for FrameSelection {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<FrameSelection, String>
+    {
+        match value.trim() {
+            "all" => Ok(FrameSelection::All),
+            "best" => Ok(FrameSelection::Best),
+            label => Frame::from_label(label).map(FrameSelection::Single)
+                .ok_or_else(|| format!("unknown frame selection '{}', expected \"+1\" .. \"-3\", \"all\" or \"best\"", label)),
+        }
+    }
+}
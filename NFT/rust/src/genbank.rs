@@ -0,0 +1,372 @@
+//! GenBank flat-file reader (https://www.ncbi.nlm.nih.gov/Sitemap/samplerecord.html): the sequence of the ORIGIN
+//! block, with its line numbers and spaces dropped, the accession, the organism and the CDS features.
+
+use std::ops::Range;
+
+use crate::annotation::Feature;
+use crate::fasta::Record;
+use crate::frame::Strand;
+use crate::{Error, Result};
+
+// column where feature locations and qualifiers start
+const FEATURE_COLUMN: usize = 21;
+
+/// One entry of a GenBank file, from LOCUS to "//".
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenBankRecord {
+    /// ID is the versioned accession, e.g. "MN908947.3", as in FASTA headers and GFF3 files; description is
+    /// the DEFINITION line.
+    pub record: Record,
+    /// Accession without version, e.g. "MN908947".
+    pub accession: String,
+    /// Species, e.g. "Severe acute respiratory syndrome coronavirus 2".
+    pub organism: Option<String>,
+    /// CDS features, on `record`.
+    pub features: Vec<Feature>,
+}
+
+/// True if the text starts with a LOCUS line.
+pub fn is_genbank(text: &str) -> bool
+{
+    text.trim_start().starts_with("LOCUS")
+}
+
+fn invalid(line: usize, message: String) -> Error
+{
+    Error::InvalidGenBank { line: line + 1, message }
+}
+
+// splits `text` at the commas outside parentheses
+fn split_top_level(text: &str) -> Vec<&str>
+{
+    let mut parts: Vec<&str> = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn position(text: &str) -> Option<usize>
+{
+    text.trim_start_matches(['<', '>']).parse().ok().filter(|&p| p > 0)
+}
+
+/// Reads a feature location, e.g. "21563..25384", "join(266..13468,13468..21555)" or "complement(<1..>120)",
+/// into 0-based forward-strand ranges in reading order, each with its strand. Partial markers are dropped;
+/// sites between two bases ("12^13") and ranges on other entries ("J00194.1:100..202") read nothing.
+pub fn parse_location(location: &str) -> Option<Vec<(Range<usize>, Strand)>>
+{
+    let location = location.trim();
+    let inner = |prefix: &str| location.strip_prefix(prefix).and_then(|rest| rest.strip_suffix(')'));
+    if let Some(inside) = inner("complement(") {
+        let mut segments = parse_location(inside)?;
+        segments.reverse();
+        for (_, strand) in &mut segments {
+            *strand = match strand {
+                Strand::Forward => Strand::Reverse,
+                Strand::Reverse => Strand::Forward,
+            };
+        }
+        return Some(segments);
+    }
+    if let Some(inside) = inner("join(").or_else(|| inner("order(")) {
+        let mut segments: Vec<(Range<usize>, Strand)> = Vec::new();
+        for part in split_top_level(inside) {
+            segments.extend(parse_location(part)?);
+        }
+        return Some(segments);
+    }
+    if location.contains(':') || location.contains('^') {
+        return Some(Vec::new());
+    }
+    let (first, last) = match location.split_once("..") {
+        Some((first, last)) => (position(first)?, position(last)?),
+        None => {
+            let base = position(location)?;
+            (base, base)
+        }
+    };
+    if last < first {
+        return None;
+    }
+    Some(vec![(first - 1..last, Strand::Forward)])
+}
+
+// a feature being read: key, location text and qualifiers, each of which may span several lines
+struct PendingFeature {
+    line: usize,
+    key: String,
+    location: String,
+    qualifiers: Vec<(String, String)>,
+}
+
+impl PendingFeature {
+    fn into_feature(self, record: &str) -> Result<Option<Feature>>
+    {
+        if self.key != "CDS" {
+            return Ok(None);
+        }
+        let segments = match parse_location(&self.location) {
+            Some(segments) => segments,
+            None => return Err(invalid(self.line, format!("cannot read location '{}'", self.location))),
+        };
+        let strand = match segments.first() {
+            Some((_, strand)) => *strand,
+            // nothing on this entry
+            None => return Ok(None),
+        };
+        let qualifier = |key: &str| self.qualifiers.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        let mut feature = Feature {
+            record: record.to_string(),
+            kind: self.key.clone(),
+            id: qualifier("protein_id").or_else(|| qualifier("locus_tag")),
+            name: qualifier("gene").or_else(|| qualifier("locus_tag")),
+            product: qualifier("product"),
+            strand,
+            segments: segments.into_iter().map(|(range, _)| range).collect(),
+        };
+
+        // bases to skip before the first full codon of a 5' partial CDS, like a GFF3 phase
+        match qualifier("codon_start").map(|value| (value.trim().parse::<usize>(), value)) {
+            Some((Ok(start @ 1..=3), _)) => feature.skip_bases(start - 1),
+            Some((_, value)) => return Err(invalid(self.line, format!("/codon_start must be 1, 2 or 3, not '{}'", value))),
+            None => {}
+        }
+        Ok(Some(feature))
+    }
+}
+
+// which block of an entry the lines belong to
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Definition,
+    Features,
+    Origin,
+}
+
+// what has been read of an entry so far
+#[derive(Default)]
+struct Entry {
+    locus: String,
+    version: Option<String>,
+    accession: String,
+    definition: String,
+    organism: Option<String>,
+    sequence: String,
+    features: Vec<PendingFeature>,
+}
+
+impl Entry {
+    fn finish(self) -> Result<GenBankRecord>
+    {
+        let id = match self.version {
+            Some(version) => version,
+            None if !self.accession.is_empty() => self.accession.clone(),
+            None => self.locus,
+        };
+        let mut features: Vec<Feature> = Vec::new();
+        for feature in self.features {
+            features.extend(feature.into_feature(&id)?);
+        }
+        let description = self.definition.trim_end_matches('.').to_string();
+        Ok(GenBankRecord {
+            record: Record { id, description, sequence: self.sequence },
+            accession: self.accession,
+            organism: self.organism,
+            features,
+        })
+    }
+}
+
+/// Reads every entry of a GenBank file. The "//" line after the last entry may be missing.
+pub fn parse(text: &str) -> Result<Vec<GenBankRecord>>
+{
+    read_lines(text.lines().map(Ok))
+}
+
+/// Same as parse, one line at a time, e.g. straight out of a decompressor.
+pub fn read_lines<I, S>(lines: I) -> Result<Vec<GenBankRecord>>
+where
+    I: Iterator<Item = Result<S>>,
+    S: AsRef<str>,
+{
+    let mut entries: Vec<GenBankRecord> = Vec::new();
+    let mut entry: Option<Entry> = None;
+    let mut section = Section::Header;
+
+    for (i, line) in lines.enumerate() {
+        let line = line?;
+        let line = line.as_ref().trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        // a keyword starts in the first column; anything else goes on with the block above
+        let keyword = if line.starts_with(' ') { "" } else { line.split_whitespace().next().unwrap_or("") };
+        let value = line.get(12..).unwrap_or("").trim();
+
+        if keyword == "LOCUS" {
+            if let Some(previous) = entry.take() {
+                entries.push(previous.finish()?);
+            }
+            entry = Some(Entry { locus: value.split_whitespace().next().unwrap_or("").to_string(), ..Entry::default() });
+            section = Section::Header;
+            continue;
+        }
+        let current = match entry.as_mut() {
+            Some(current) => current,
+            None => return Err(invalid(i, "expected a LOCUS line".to_string())),
+        };
+
+        match keyword {
+            "DEFINITION" => {
+                section = Section::Definition;
+                current.definition = value.to_string();
+            }
+            "ACCESSION" => {
+                section = Section::Header;
+                current.accession = value.split_whitespace().next().unwrap_or("").to_string();
+            }
+            "VERSION" => {
+                section = Section::Header;
+                current.version = value.split_whitespace().next().map(|v| v.to_string());
+            }
+            "FEATURES" => section = Section::Features,
+            "ORIGIN" => section = Section::Origin,
+            "//" => {
+                if let Some(finished) = entry.take() {
+                    entries.push(finished.finish()?);
+                }
+                section = Section::Header;
+            }
+            "" => match section {
+                Section::Definition => {
+                    current.definition.push(' ');
+                    current.definition.push_str(line.trim());
+                }
+                Section::Features => read_feature_line(line, i, &mut current.features)?,
+                // line numbers and blocks of ten bases
+                Section::Origin => current.sequence.extend(line.chars().filter(|c| c.is_ascii_alphabetic())),
+                Section::Header => {
+                    if line.trim_start().starts_with("ORGANISM") && current.organism.is_none() {
+                        current.organism = Some(value.to_string());
+                    }
+                }
+            },
+            // REFERENCE, COMMENT, ...
+            _ => section = Section::Header,
+        }
+    }
+
+    if let Some(last) = entry {
+        entries.push(last.finish()?);
+    }
+    Ok(entries)
+}
+
+// one line of the FEATURES block: a new feature, a new qualifier, or the continuation of either
+fn read_feature_line(line: &str, index: usize, pending: &mut Vec<PendingFeature>) -> Result<()>
+{
+    let key = line.get(5..FEATURE_COLUMN).unwrap_or("").trim();
+    let rest = line.get(FEATURE_COLUMN..).unwrap_or("").trim();
+    if !key.is_empty() {
+        pending.push(PendingFeature { line: index, key: key.to_string(), location: rest.to_string(), qualifiers: Vec::new() });
+        return Ok(());
+    }
+    let feature = match pending.last_mut() {
+        Some(feature) => feature,
+        None => return Err(invalid(index, "qualifier before any feature".to_string())),
+    };
+    if let Some(qualifier) = rest.strip_prefix('/') {
+        let (key, value) = qualifier.split_once('=').unwrap_or((qualifier, ""));
+        feature.qualifiers.push((key.to_string(), value.trim_matches('"').to_string()));
+    }
+    else if let Some((key, value)) = feature.qualifiers.last_mut() {
+        // a quoted value going on: words of a /product are separated by a space, residues of a /translation are not
+        if key != "translation" && !value.is_empty() {
+            value.push(' ');
+        }
+        value.push_str(rest.trim_matches('"'));
+    }
+    else {
+        feature.location.push_str(rest);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "\
+LOCUS       TEST                      40 bp    DNA     linear   VRL 01-JAN-2020
+DEFINITION  Test sequence.
+ACCESSION   TEST
+VERSION     TEST.1
+FEATURES             Location/Qualifiers
+     CDS             join(1..9,
+                     12..20)
+                     /gene=\"a\"
+     CDS             complement(join(21..26,30..>40))
+                     /gene=\"b\"
+ORIGIN
+        1 atgaaacgtg gcttttaagc ttaaaaccat ggcttcatgc
+//
+";
+
+    #[test]
+    fn joined_and_complemented_locations()
+    {
+        use Strand::{Forward, Reverse};
+        assert_eq!(parse_location("21563..25384"), Some(vec![(21562..25384, Forward)]));
+        assert_eq!(parse_location("join(266..13468,13468..21555)"), Some(vec![(265..13468, Forward), (13467..21555, Forward)]));
+        // reading order on the reverse strand is from the last segment back
+        assert_eq!(parse_location("complement(join(1..10,20..30))"), Some(vec![(19..30, Reverse), (0..10, Reverse)]));
+        assert_eq!(parse_location("join(complement(20..30),complement(1..10))"), Some(vec![(19..30, Reverse), (0..10, Reverse)]));
+        assert_eq!(parse_location("complement(<1..>120)"), Some(vec![(0..120, Reverse)]));
+        assert_eq!(parse_location("join(5,J00194.1:100..202)"), Some(vec![(4..5, Forward)]));
+        assert_eq!(parse_location("12^13"), Some(Vec::new()));
+        assert_eq!(parse_location("30..20"), None);
+        assert_eq!(parse_location("join(1..10,x)"), None);
+        assert_eq!(parse_location("0..10"), None);
+    }
+
+    #[test]
+    fn locations_spanning_lines()
+    {
+        let records = parse(RECORD).unwrap();
+        let features = &records[0].features;
+        assert_eq!(records[0].record.id, "TEST.1");
+        assert_eq!(records[0].record.sequence.len(), 40);
+        assert_eq!((features[0].name.as_deref(), features[0].strand), (Some("a"), Strand::Forward));
+        assert_eq!(features[0].segments, vec![0..9, 11..20]);
+        assert_eq!((features[1].name.as_deref(), features[1].strand), (Some("b"), Strand::Reverse));
+        assert_eq!(features[1].segments, vec![29..40, 20..26]);
+    }
+
+    #[test]
+    fn codon_start_skips_into_the_first_segment()
+    {
+        let partial = RECORD
+            .replace("                     /gene=\"a\"\n", "                     /gene=\"a\"\n                     /codon_start=2\n")
+            .replace("                     /gene=\"b\"\n", "                     /gene=\"b\"\n                     /codon_start=3\n");
+        let features = &parse(&partial).unwrap()[0].features;
+        assert_eq!(features[0].segments, vec![1..9, 11..20]);
+        // on the reverse strand the first codon is read from the end of the first segment
+        assert_eq!(features[1].segments, vec![29..38, 20..26]);
+
+        let invalid = RECORD.replace("                     /gene=\"a\"\n", "                     /codon_start=4\n");
+        assert!(matches!(parse(&invalid), Err(Error::InvalidGenBank { line: 6, .. })));
+    }
+}
@@ -0,0 +1,282 @@
+//! NCBI translation tables (https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi).
+//! Each table is given the way NCBI prints it: one residue letter per codon, with codons
+//! ordered TTT, TTC, TTA, TTG, TCT, ... GGG, and a matching line that marks start codons with 'M'.
+
+use crate::nucleotide::{self, Nucleotide};
+use crate::amino_acid::AminoAcid;
+
+pub struct GeneticCode {
+    pub id: u8,
+    pub name: &'static str,
+    amino_acids: &'static [u8; 64],
+    starts: &'static [u8; 64],
+}
+
+pub const STANDARD: GeneticCode = GeneticCode {
+    id: 1,
+    name: "Standard",
+    amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    starts:      b"---M------**--*----M---------------M----------------------------",
+};
+
+pub static TABLES: [GeneticCode; 26] = [
+    STANDARD,
+    GeneticCode {
+        id: 2,
+        name: "Vertebrate Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+        starts:      b"----------**--------------------MMMM----------**---M------------",
+    },
+    GeneticCode {
+        id: 3,
+        name: "Yeast Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**----------------------MM---------------M------------",
+    },
+    GeneticCode {
+        id: 4,
+        name: "Mold, Protozoan, and Coelenterate Mitochondrial; Mycoplasma; Spiroplasma",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--MM------**-------M------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 5,
+        name: "Invertebrate Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+        starts:      b"---M------**--------------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 6,
+        name: "Ciliate, Dasycladacean and Hexamita Nuclear",
+        amino_acids: b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--------------*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 9,
+        name: "Echinoderm and Flatworm Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts:      b"----------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 10,
+        name: "Euplotid Nuclear",
+        amino_acids: b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**-----------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 11,
+        name: "Bacterial, Archaeal and Plant Plastid",
+        amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"---M------**--*----M------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 12,
+        name: "Alternative Yeast Nuclear",
+        amino_acids: b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**--*----M---------------M----------------------------",
+    },
+    GeneticCode {
+        id: 13,
+        name: "Ascidian Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+        starts:      b"---M------**----------------------MM---------------M------------",
+    },
+    GeneticCode {
+        id: 14,
+        name: "Alternative Flatworm Mitochondrial",
+        amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts:      b"-----------*-----------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 16,
+        name: "Chlorophycean Mitochondrial",
+        amino_acids: b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------*---*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 21,
+        name: "Trematode Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+        starts:      b"----------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 22,
+        name: "Scenedesmus obliquus Mitochondrial",
+        amino_acids: b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"------*---*---*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 23,
+        name: "Thraustochytrium Mitochondrial",
+        amino_acids: b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--*-------**--*-----------------M--M---------------M------------",
+    },
+    GeneticCode {
+        id: 24,
+        name: "Rhabdopleuridae Mitochondrial",
+        amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        starts:      b"---M------**-------M---------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 25,
+        name: "Candidate Division SR1 and Gracilibacteria",
+        amino_acids: b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"---M------**-----------------------M---------------M------------",
+    },
+    GeneticCode {
+        id: 26,
+        name: "Pachysolen tannophilus Nuclear",
+        amino_acids: b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**--*----M---------------M----------------------------",
+    },
+    GeneticCode {
+        id: 27,
+        name: "Karyorelict Nuclear",
+        amino_acids: b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--------------*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 28,
+        name: "Condylostoma Nuclear",
+        amino_acids: b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**--*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 29,
+        name: "Mesodinium Nuclear",
+        amino_acids: b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--------------*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 30,
+        name: "Peritrich Nuclear",
+        amino_acids: b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"--------------*--------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 31,
+        name: "Blastocrithidia Nuclear",
+        amino_acids: b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"----------**-----------------------M----------------------------",
+    },
+    GeneticCode {
+        id: 32,
+        name: "Balanophoraceae Plastid",
+        amino_acids: b"FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+        starts:      b"---M------*---*----M------------MMMM---------------M------------",
+    },
+    GeneticCode {
+        id: 33,
+        name: "Cephalodiscidae Mitochondrial",
+        amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG",
+        starts:      b"---M-------*-------M---------------M---------------M------------",
+    },
+];
+
+// position of an unambiguous base in NCBI's T, C, A, G ordering
+fn base_index(nuc: Nucleotide) -> usize
+{
+    match nuc {
+        Nucleotide::T => 0,
+        Nucleotide::C => 1,
+        Nucleotide::A => 2,
+        _ => 3,
+    }
+}
+
+fn codon_index(codon: [Nucleotide; 3]) -> usize
+{
+    16 * base_index(codon[0]) + 4 * base_index(codon[1]) + base_index(codon[2])
+}
+
+impl GeneticCode {
+    /// Looks up an NCBI table by its number.
+    pub fn from_id(id: u8) -> Option<&'static GeneticCode>
+    {
+        TABLES.iter().find(|code| code.id == id)
+    }
+
+    /// Translates one codon. An ambiguous codon (e.g. "CTN") only resolves if every codon
+    /// it can stand for gives the same residue, and is AminoAcid::Unknown otherwise.
+    pub fn translate(&self, codon: [Nucleotide; 3]) -> AminoAcid
+    {
+        let mut residues = nucleotide::expand_codon(codon).into_iter()
+            .map(|c| AminoAcid::from_one_letter(self.amino_acids[codon_index(c)] as char).unwrap_or(AminoAcid::Unknown));
+        let first = residues.next().unwrap_or(AminoAcid::Unknown);
+        if residues.all(|aa| aa == first) {
+            first
+        }
+        else {
+            AminoAcid::Unknown
+        }
+    }
+
+    /// True if the codon can initiate translation: ATG, or with `alternative` any start codon of
+    /// this table (e.g. GTG and TTG in table 11). Ambiguous codons only count when every codon
+    /// they can stand for is a start codon.
+    pub fn is_start(&self, codon: [Nucleotide; 3], alternative: bool) -> bool
+    {
+        if !alternative {
+            return codon == [Nucleotide::A, Nucleotide::T, Nucleotide::G];
+        }
+        nucleotide::expand_codon(codon).into_iter().all(|c| self.starts[codon_index(c)] == b'M')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codon(text: &str) -> [Nucleotide; 3]
+    {
+        let bases: Vec<Nucleotide> = text.chars().map(|c| Nucleotide::from_char(c).unwrap()).collect();
+        [bases[0], bases[1], bases[2]]
+    }
+
+    fn translate(id: u8, text: &str) -> AminoAcid
+    {
+        GeneticCode::from_id(id).unwrap().translate(codon(text))
+    }
+
+    #[test]
+    fn every_ncbi_table()
+    {
+        let ids: Vec<u8> = (1..=6).chain(9..=14).chain([16]).chain(21..=33).collect();
+        assert!(ids.iter().all(|&id| GeneticCode::from_id(id).is_some_and(|code| code.id == id)));
+        assert_eq!(TABLES.len(), ids.len());
+        // retired numbers
+        assert!([0, 7, 8, 15, 17, 18, 19, 20, 34].iter().all(|&id| GeneticCode::from_id(id).is_none()));
+    }
+
+    #[test]
+    fn table_specific_codons()
+    {
+        use AminoAcid::{Arg, Stop, Trp};
+        for (text, expected) in [("TGA", [Stop, Trp, Trp, Trp, Stop]), ("AGA", [Arg, Stop, Arg, Arg, Arg]), ("AGG", [Arg, Stop, Arg, Arg, Arg])] {
+            let found: Vec<AminoAcid> = [1, 2, 3, 4, 11].iter().map(|&id| translate(id, text)).collect();
+            assert_eq!(found, expected, "{}", text);
+        }
+        assert_eq!(translate(32, "TAG"), Trp);
+        assert_eq!(translate(32, "TAA"), Stop);
+    }
+
+    #[test]
+    fn ambiguous_codons_and_starts()
+    {
+        // CTN is Leu in the standard code, Thr in yeast mitochondria; TAR is a stop but TRG is a stop or Trp
+        assert_eq!(translate(1, "CTN"), AminoAcid::Leu);
+        assert_eq!(translate(3, "CTN"), AminoAcid::Thr);
+        assert_eq!(translate(1, "TAR"), AminoAcid::Stop);
+        assert_eq!(translate(1, "TRG"), AminoAcid::Unknown);
+
+        let bacterial = GeneticCode::from_id(11).unwrap();
+        assert!(bacterial.is_start(codon("ATG"), false));
+        assert!(!bacterial.is_start(codon("GTG"), false));
+
+        // alternative starts of tables 1, 2, 3 and 11
+        for (text, expected) in [("ATA", [false, true, true, true]), ("GTG", [false, true, true, true]), ("TTG", [true, false, false, true])] {
+            let found: Vec<bool> = [1, 2, 3, 11].iter().map(|&id| GeneticCode::from_id(id).unwrap().is_start(codon(text), true)).collect();
+            assert_eq!(found, expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! HGVS protein notation (https://varnomen.hgvs.org/recommendations/protein/) of the effect of a variant,
+//! e.g. "p.Asp614Gly", and its one-letter short form, e.g. "D614G".
+
+use serde::Serialize;
+
+use crate::amino_acid::AminoAcid;
+use crate::variant::{Effect, OrfEffect};
+
+/// A protein change, ready to show in a legend or a tooltip.
+#[derive(Debug, Clone, Serialize)]
+pub struct Mutation {
+    /// Full HGVS notation, e.g. "p.Asp614Gly".
+    pub hgvs: String,
+    /// One-letter form, e.g. "D614G".
+    pub label: String,
+    pub effect: Effect,
+    /// Index of the residue of the sample protein the change is drawn on.
+    pub residue: usize,
+    /// First vertex of that residue in the vertex buffer.
+    pub vertex: usize,
+}
+
+// residue names in one of the two styles
+struct Style {
+    name: fn(AminoAcid) -> String,
+    stop: &'static str,
+}
+
+const THREE_LETTER: Style = Style { name: |aa| aa.three_letter().to_string(), stop: "Ter" };
+const ONE_LETTER: Style = Style { name: |aa| aa.one_letter().to_string(), stop: "*" };
+
+impl Style {
+    fn names(&self, residues: &[AminoAcid]) -> String
+    {
+        residues.iter().map(|&aa| (self.name)(aa)).collect()
+    }
+
+    // "Gly9" or "Gly9_Lys11", for the reference residues from 1-based `first` on; the position right after the
+    // protein is its stop codon
+    fn range(&self, reference: &[AminoAcid], first: usize, count: usize) -> String
+    {
+        let at = |position: usize| {
+            let aa = reference.get(position - 1).copied().unwrap_or(AminoAcid::Stop);
+            format!("{}{}", (self.name)(aa), position)
+        };
+        if count == 1 {
+            at(first)
+        }
+        else {
+            format!("{}_{}", at(first), at(first + count - 1))
+        }
+    }
+
+    // "Ter23": where the new stop codon is, counting the first changed residue as 1; "Ter?" when translation
+    // runs off the end of the sequence
+    fn new_stop(&self, residues: &[AminoAcid]) -> String
+    {
+        match residues.iter().position(|&aa| aa == AminoAcid::Stop) {
+            Some(i) => format!("{}{}", self.stop, i + 1),
+            None => format!("{}?", self.stop),
+        }
+    }
+
+    fn describe(&self, effect: &OrfEffect, reference: &[AminoAcid]) -> String
+    {
+        let first = effect.residue;
+        let removed = &effect.reference_residues;
+        let added = &effect.alternate_residues;
+        match effect.effect {
+            // past the stop codon, e.g. after a stop inside the CDS: there is no residue to place the change on
+            _ if first > reference.len() + 1 => "?".to_string(),
+            Effect::Synonymous => format!("{}=", self.range(reference, first, 1)),
+            // the start codon is gone: what gets translated, if anything, cannot be predicted
+            _ if first == 1 && removed.first() == Some(&AminoAcid::Met) => format!("{}?", self.range(reference, 1, 1)),
+            Effect::Nonsense => format!("{}{}", self.range(reference, first, 1), self.stop),
+            Effect::Frameshift => {
+                match added.first() {
+                    Some(&AminoAcid::Stop) => format!("{}{}", self.range(reference, first, 1), self.stop),
+                    Some(&aa) => format!("{}{}fs{}", self.range(reference, first, 1), (self.name)(aa), self.new_stop(added)),
+                    None => format!("{}fs", self.range(reference, first, 1)),
+                }
+            }
+            Effect::StopLoss => {
+                let new = added.first().copied().unwrap_or(AminoAcid::Unknown);
+                format!("{}{}{}ext{}", self.stop, first, (self.name)(new), self.new_stop(added))
+            }
+            Effect::Missense => {
+                if removed.is_empty() {
+                    let duplicated = first > added.len() && reference.get(first - 1 - added.len()..first - 1) == Some(&added[..]);
+                    if duplicated {
+                        format!("{}dup", self.range(reference, first - added.len(), added.len()))
+                    }
+                    else {
+                        // between the two residues around the insertion
+                        format!("{}ins{}", self.range(reference, first.max(2) - 1, 2), self.names(added))
+                    }
+                }
+                else if added.is_empty() {
+                    format!("{}del", self.range(reference, first, removed.len()))
+                }
+                else if removed.len() == 1 && added.len() == 1 {
+                    format!("{}{}", self.range(reference, first, 1), self.names(added))
+                }
+                else {
+                    format!("{}delins{}", self.range(reference, first, removed.len()), self.names(added))
+                }
+            }
+        }
+    }
+}
+
+/// HGVS notation of `effect`, e.g. "p.Asp614Gly", "p.Gly9_Lys11del" or "p.Arg97ProfsTer23".
+/// `reference` is the reference protein the effect was found on; a change past its stop codon is "p.?".
+pub fn hgvs(effect: &OrfEffect, reference: &[AminoAcid]) -> String
+{
+    format!("p.{}", THREE_LETTER.describe(effect, reference))
+}
+
+/// One-letter form of `effect`, e.g. "D614G", "G9_K11del" or "R97Pfs*23"; "?" past the stop codon.
+pub fn label(effect: &OrfEffect, reference: &[AminoAcid]) -> String
+{
+    ONE_LETTER.describe(effect, reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{Frame, Strand};
+    use crate::genetic_code::GeneticCode;
+    use crate::nucleotide::Nucleotide;
+    use crate::orf;
+    use crate::variant::{call_variants, effect};
+
+    // ATG AAA CGT GGC TTT CTG GAA TAA: Met Lys Arg Gly Phe Leu Glu, and a flank with stop codons in other frames
+    const REFERENCE: &str = "ATGAAACGTGGCTTTCTGGAATAACTAGATGA";
+
+    // HGVS notation and label of `sample`, `REFERENCE` with `removed` bases at `at` replaced by `added`
+    fn labels(at: usize, removed: usize, added: &str) -> (String, String)
+    {
+        let sample = format!("{}{}{}", &REFERENCE[..at], added, &REFERENCE[at + removed..]);
+        let code = GeneticCode::from_id(1).unwrap();
+        let bases: Vec<Nucleotide> = REFERENCE.chars().filter_map(Nucleotide::from_char).collect();
+        let cds = 0..24;
+        let orf = orf::from_segments("ref", &bases, vec![cds], Strand::Forward, true, code, None);
+        let variants = call_variants("ref", &sample, REFERENCE);
+        assert_eq!(variants.len(), 1);
+        let effect = effect(&variants[0], &orf, &bases, code).unwrap();
+        (hgvs(&effect, &orf.protein), label(&effect, &orf.protein))
+    }
+
+    #[test]
+    fn indels_and_frameshifts()
+    {
+        let names = |hgvs: &str, label: &str| (hgvs.to_string(), label.to_string());
+        assert_eq!(labels(9, 3, ""), names("p.Gly4del", "G4del"));
+        assert_eq!(labels(9, 6, ""), names("p.Gly4_Phe5del", "G4_F5del"));
+        assert_eq!(labels(12, 0, "GAT"), names("p.Gly4_Phe5insAsp", "G4_F5insD"));
+        assert_eq!(labels(12, 0, "GGC"), names("p.Gly4dup", "G4dup"));
+        assert_eq!(labels(9, 3, "GAT"), names("p.Gly4Asp", "G4D"));
+        // GCT TTC TGG AAT AAC TAG: Ala Phe Trp Asn Asn and a stop, the 6th codon from the one that changed
+        assert_eq!(labels(9, 1, ""), names("p.Gly4AlafsTer6", "G4Afs*6"));
+        assert_eq!(labels(9, 3, "TAA"), names("p.Gly4Ter", "G4*"));
+    }
+
+    #[test]
+    fn insertion_past_an_internal_stop()
+    {
+        // a CDS with a stop in the middle: its protein ends there, before the residue the insertion is at
+        let protein = [AminoAcid::Met, AminoAcid::Lys];
+        let effect = OrfEffect {
+            record: "ref".to_string(),
+            name: None,
+            frame: Frame::FORWARD_1,
+            start: 0,
+            end: 30,
+            effect: Effect::Missense,
+            residue: 6,
+            reference_residues: Vec::new(),
+            alternate_residues: vec![AminoAcid::Gly],
+        };
+        assert_eq!(hgvs(&effect, &protein), "p.?");
+        assert_eq!(label(&effect, &protein), "?");
+    }
+}
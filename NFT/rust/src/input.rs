@@ -0,0 +1,226 @@
+//! Sequence input, whatever the format: FASTA, multi-FASTA, raw sequence, FASTQ or GenBank flat file, as text or
+//! as gzip or BGZF-compressed bytes, and UCSC .2bit files.
+
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+
+use flate2::read::MultiGzDecoder;
+
+use crate::annotation::Feature;
+use crate::fasta::{self, Record};
+use crate::fastq;
+use crate::frame::{Frame, Strand};
+use crate::genbank;
+use crate::region;
+use crate::twobit::{self, TwoBitFile};
+use crate::{Error, Result};
+
+// first two bytes of a gzip member
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// FASTA, multi-FASTA or a raw sequence.
+    Fasta,
+    Fastq,
+    GenBank,
+}
+
+/// The format of `text`, from its first line.
+pub fn detect(text: &str) -> Format
+{
+    if genbank::is_genbank(text) {
+        Format::GenBank
+    }
+    else if fastq::is_fastq(text) {
+        Format::Fastq
+    }
+    else {
+        Format::Fasta
+    }
+}
+
+/// The records of an input, with the CDS features it carries, if its format has any.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    pub records: Vec<Record>,
+    pub features: Vec<Feature>,
+    /// Phred score of each base of each record, for formats that have them; empty otherwise.
+    pub qualities: Vec<Vec<u8>>,
+    /// What part of its sequence each record holds, when some only hold part of it (see parse_bytes_in);
+    /// empty otherwise.
+    pub slices: Vec<Option<Slice>>,
+}
+
+/// The part of a longer sequence a record holds, e.g. a region read from a .2bit file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slice {
+    /// Forward-strand range of the sequence.
+    pub range: Range<usize>,
+    /// Length of the whole sequence.
+    pub length: usize,
+}
+
+impl Slice {
+    /// `frame` of the record, as a frame of the whole sequence.
+    pub fn frame(&self, frame: Frame) -> Frame
+    {
+        let skipped = match frame.strand {
+            Strand::Forward => self.range.start,
+            Strand::Reverse => self.length - self.range.end,
+        };
+        Frame { strand: frame.strand, offset: ((frame.offset as usize + skipped) % 3) as u8 }
+    }
+}
+
+impl Input {
+    /// Replaces the bases scored below `min_quality` by N. Records without scores are left alone.
+    pub fn mask(&mut self, min_quality: u8)
+    {
+        if min_quality == 0 {
+            return;
+        }
+        for (record, quality) in self.records.iter_mut().zip(&self.qualities) {
+            record.sequence = fastq::mask(&record.sequence, quality, min_quality);
+        }
+    }
+}
+
+/// Reads the records of `text`, in whatever format it is.
+pub fn parse(text: &str) -> Result<Input>
+{
+    read(text.as_bytes())
+}
+
+// the lines of `reader`, with read and UTF-8 errors as crate errors
+fn lines<R: BufRead>(reader: R) -> impl Iterator<Item = Result<String>>
+{
+    reader.lines().map(|line| line.map_err(|e| Error::InvalidInput { message: e.to_string() }))
+}
+
+/// Reads the records of text coming out of `reader`, in whatever format it is, one line at a time: only the
+/// records are held in memory, never the whole text.
+pub fn read<R: BufRead>(mut reader: R) -> Result<Input>
+{
+    // the first bytes tell the format; leading blank lines are skipped by every parser
+    let start = reader.fill_buf().map_err(|e| Error::InvalidInput { message: e.to_string() })?;
+    let start = String::from_utf8_lossy(&start[..start.len().min(64)]).into_owned();
+    match detect(&start) {
+        Format::Fasta => Ok(Input { records: fasta::read_lines(lines(reader))?, ..Input::default() }),
+        Format::Fastq => {
+            let mut input = Input::default();
+            for read in fastq::read_lines(lines(reader))? {
+                input.records.push(read.record);
+                input.qualities.push(read.quality);
+            }
+            Ok(input)
+        }
+        Format::GenBank => {
+            let mut input = Input::default();
+            for entry in genbank::read_lines(lines(reader))? {
+                input.records.push(entry.record);
+                input.features.extend(entry.features);
+            }
+            Ok(input)
+        }
+    }
+}
+
+/// How a byte input is compressed, from its magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    /// Blocked gzip (https://samtools.github.io/hts-specs/SAMv1.pdf, section 4.1), as written by bgzip for
+    /// indexed files: a series of gzip members, each with a "BC" extra field.
+    Bgzf,
+    /// bzip2, xz or zstd, which are not supported.
+    Other(&'static str),
+}
+
+/// The compression of `bytes`, from the magic bytes they start with.
+pub fn detect_compression(bytes: &[u8]) -> Compression
+{
+    if bytes.starts_with(&GZIP_MAGIC) {
+        // FEXTRA flag set, and the first extra subfield is BC
+        if bytes.len() >= 14 && bytes[3] & 0x04 != 0 && &bytes[12..14] == b"BC" {
+            Compression::Bgzf
+        }
+        else {
+            Compression::Gzip
+        }
+    }
+    else if bytes.starts_with(b"BZh") {
+        Compression::Other("bzip2")
+    }
+    else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Compression::Other("xz")
+    }
+    else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Compression::Other("zstd")
+    }
+    else {
+        Compression::None
+    }
+}
+
+/// Reads the records of `bytes`: text in any format parse() takes, plain or gzip / BGZF-compressed, or a UCSC
+/// .2bit file. Compressed input is decompressed as it is parsed.
+pub fn parse_bytes(bytes: &[u8]) -> Result<Input>
+{
+    if twobit::is_twobit(bytes) {
+        return Ok(Input { records: TwoBitFile::open(bytes)?.records()?, ..Input::default() });
+    }
+    match detect_compression(bytes) {
+        Compression::None => read(bytes),
+        // a BGZF file is a valid multi-member gzip file
+        Compression::Gzip | Compression::Bgzf => read(BufReader::new(MultiGzDecoder::new(bytes))),
+        Compression::Other(name) => Err(Error::InvalidInput { message: format!("{}-compressed input is not supported, use gzip", name) }),
+    }
+}
+
+/// Same as parse_bytes, but of a .2bit file only the bases of `region` (see Options::region) are read: the input
+/// is a single record of that part of the sequence, the rest of the file is never decoded. Proteins are then only
+/// looked for inside the region.
+pub fn parse_bytes_in(bytes: &[u8], region: Option<&str>) -> Result<Input>
+{
+    let text = match region {
+        Some(text) if twobit::is_twobit(bytes) => text,
+        _ => return parse_bytes(bytes),
+    };
+    let file = TwoBitFile::open(bytes)?;
+    let lengths: Vec<(String, usize)> = file.names()
+        .map(|name| Ok((name.to_string(), file.length(name)?)))
+        .collect::<Result<_>>()?;
+    // a .2bit file has no features, so the region can only be coordinates
+    let region = region::resolve_among(text, &[], &lengths)?;
+    let (name, length) = &lengths[region.index];
+    Ok(Input {
+        records: vec![file.record(name, Some(region.range.clone()))?],
+        slices: vec![Some(Slice { range: region.range, length: *length })],
+        ..Input::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::twobit::tests::encode;
+
+    #[test]
+    fn region_of_twobit_file()
+    {
+        let file = encode(&[("chr1", "ACGTACGTAC"), ("chr2", "TTTTGGGGCCCCAAAA")]);
+        let input = parse_bytes_in(&file, Some("chr2:6-11")).unwrap();
+        assert_eq!(input.records.len(), 1);
+        assert_eq!((input.records[0].id.as_str(), input.records[0].sequence.as_str()), ("chr2", "GGGCCC"));
+        let slice = input.slices[0].clone().unwrap();
+        assert_eq!(slice, Slice { range: 5..11, length: 16 });
+        // +1 of the slice starts 5 bases in, -1 of the slice 5 bases from the end
+        assert_eq!(slice.frame(Frame::FORWARD_1).offset, 2);
+        assert_eq!(slice.frame(Frame { strand: Strand::Reverse, offset: 0 }).offset, 2);
+
+        assert_eq!(parse_bytes_in(&file, None).unwrap().records.len(), 2);
+        assert!(parse_bytes_in(&file, Some("chr3:1-2")).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Layout of the vertex buffer handed to a renderer: planar (each attribute in a block of its own, as
+//! load_sequence returns it) or interleaved (the attributes of a vertex side by side), with optional attributes and
+//! quantized normals. The buffer comes with a descriptor of where each attribute is, so it can be bound as it is.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Arrangement {
+    /// All positions, then all normals, and so on.
+    Planar,
+    /// Position, normal, ... of the first vertex, then of the second one.
+    Interleaved,
+}
+
+/// How normals are stored. The names are those of WebGPU vertex formats; the fourth component is padding, to keep
+/// every attribute 4-byte aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalFormat {
+    /// Three f32.
+    Float32x3,
+    /// Three i8, -1..1 mapped to -127..127.
+    Snorm8x4,
+    /// Three u16, -1..1 mapped to 0..65535: read normalized, then as 2 * value - 1.
+    Unorm16x4,
+}
+
+/// What the vertex buffer holds and how; part of the load options.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct VertexLayout {
+    pub arrangement: Arrangement,
+    /// Format of the normals, null to leave them out.
+    pub normals: Option<NormalFormat>,
+    pub colors: bool,
+    /// Also the pick ID of each vertex, as a u32 (see Visualization::pick_ids).
+    pub pick_ids: bool,
+}
+
+impl Default for VertexLayout {
+    // the layout of load_sequence
+    fn default() -> VertexLayout
+    {
+        VertexLayout { arrangement: Arrangement::Planar, normals: Some(NormalFormat::Float32x3), colors: true, pick_ids: false }
+    }
+}
+
+/// Where an attribute is in the buffer: vertex i starts at byte offset + i * stride.
+#[derive(Debug, Clone, Serialize)]
+pub struct Attribute {
+    pub name: &'static str,
+    /// WebGPU vertex format, e.g. "float32x3".
+    pub format: &'static str,
+    /// Number of components, padding included, and their type: "f32", "i8", "u16" or "u32".
+    pub components: usize,
+    pub component_type: &'static str,
+    /// True if integer components are read as fixed point, to -1..1 (signed) or 0..1 (unsigned).
+    pub normalized: bool,
+    pub offset: usize,
+    pub stride: usize,
+    /// Scale and bias that give the value back once read, null if it is read as it is.
+    pub remap: Option<[f32; 2]>,
+}
+
+/// The layout of a vertex buffer, as a renderer needs it to bind the attributes. Bytes are little-endian.
+#[derive(Debug, Clone, Serialize)]
+pub struct LayoutDescriptor {
+    pub arrangement: Arrangement,
+    pub vertex_count: usize,
+    pub byte_length: usize,
+    /// Bytes per vertex when interleaved, null when planar: each attribute then has a stride of its own.
+    pub stride: Option<usize>,
+    pub attributes: Vec<Attribute>,
+}
+
+// an attribute of the layout
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Position,
+    Normal(NormalFormat),
+    Color,
+    PickId,
+}
+
+impl Kind {
+    fn attribute(self) -> Attribute
+    {
+        let (name, format, components, component_type, normalized, remap) = match self {
+            Kind::Position => ("position", "float32x3", 3, "f32", false, None),
+            Kind::Normal(NormalFormat::Float32x3) => ("normal", "float32x3", 3, "f32", false, None),
+            Kind::Normal(NormalFormat::Snorm8x4) => ("normal", "snorm8x4", 4, "i8", true, None),
+            Kind::Normal(NormalFormat::Unorm16x4) => ("normal", "unorm16x4", 4, "u16", true, Some([2.0, -1.0])),
+            Kind::Color => ("color", "float32x3", 3, "f32", false, None),
+            Kind::PickId => ("pick_id", "uint32", 1, "u32", false, None),
+        };
+        Attribute { name, format, components, component_type, normalized, offset: 0, stride: 0, remap }
+    }
+
+    // bytes per vertex
+    fn size(self) -> usize
+    {
+        match self {
+            Kind::Normal(NormalFormat::Snorm8x4) => 4,
+            Kind::Normal(NormalFormat::Unorm16x4) => 8,
+            Kind::PickId => 4,
+            _ => 12,
+        }
+    }
+}
+
+// the attributes `layout` asks for, in buffer order
+fn kinds(layout: &VertexLayout) -> Vec<Kind>
+{
+    let mut kinds = vec![Kind::Position];
+    if let Some(format) = layout.normals {
+        kinds.push(Kind::Normal(format));
+    }
+    if layout.colors {
+        kinds.push(Kind::Color);
+    }
+    if layout.pick_ids {
+        kinds.push(Kind::PickId);
+    }
+    kinds
+}
+
+/// Where each attribute of `vertex_count` vertices laid out as `layout` says is.
+pub fn describe(vertex_count: usize, layout: &VertexLayout) -> LayoutDescriptor
+{
+    let kinds = kinds(layout);
+    let vertex_size: usize = kinds.iter().map(|kind| kind.size()).sum();
+    let mut offset = 0;
+    let mut attributes: Vec<Attribute> = Vec::new();
+    for kind in kinds {
+        attributes.push(match layout.arrangement {
+            Arrangement::Planar => Attribute { offset, stride: kind.size(), ..kind.attribute() },
+            Arrangement::Interleaved => Attribute { offset, stride: vertex_size, ..kind.attribute() },
+        });
+        offset += match layout.arrangement {
+            Arrangement::Planar => kind.size() * vertex_count,
+            Arrangement::Interleaved => kind.size(),
+        };
+    }
+    LayoutDescriptor {
+        arrangement: layout.arrangement,
+        vertex_count,
+        byte_length: vertex_size * vertex_count,
+        stride: match layout.arrangement {
+            Arrangement::Planar => None,
+            Arrangement::Interleaved => Some(vertex_size),
+        },
+        attributes,
+    }
+}
+
+/// Writes the vertices given as planar arrays (three floats per vertex for `positions`, `normals` and `colors`)
+/// into a buffer laid out as `layout` says.
+pub fn write(positions: &[f32], normals: &[f32], colors: &[f32], pick_ids: &[u32], layout: &VertexLayout) -> (Vec<u8>, LayoutDescriptor)
+{
+    let vertex_count = positions.len() / 3;
+    let kinds = kinds(layout);
+    let write_one = |kind: Kind, vertex: usize, out: &mut Vec<u8>| {
+        let xyz = |values: &[f32]| [values[3 * vertex], values[3 * vertex + 1], values[3 * vertex + 2]];
+        match kind {
+            Kind::Position | Kind::Color | Kind::Normal(NormalFormat::Float32x3) => {
+                let values = match kind {
+                    Kind::Position => positions,
+                    Kind::Color => colors,
+                    _ => normals,
+                };
+                for x in xyz(values) {
+                    out.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+            Kind::Normal(NormalFormat::Snorm8x4) => {
+                for x in xyz(normals) {
+                    out.push((x.clamp(-1.0, 1.0) * 127.0).round() as i8 as u8);
+                }
+                out.push(0);
+            }
+            Kind::Normal(NormalFormat::Unorm16x4) => {
+                for x in xyz(normals) {
+                    let value = ((x.clamp(-1.0, 1.0) * 0.5 + 0.5) * 65535.0).round() as u16;
+                    out.extend_from_slice(&value.to_le_bytes());
+                }
+                out.extend_from_slice(&[0, 0]);
+            }
+            Kind::PickId => out.extend_from_slice(&pick_ids[vertex].to_le_bytes()),
+        }
+    };
+
+    let descriptor = describe(vertex_count, layout);
+    let mut bytes: Vec<u8> = Vec::with_capacity(descriptor.byte_length);
+    match layout.arrangement {
+        Arrangement::Planar => {
+            for &kind in &kinds {
+                for vertex in 0..vertex_count {
+                    write_one(kind, vertex, &mut bytes);
+                }
+            }
+        }
+        Arrangement::Interleaved => {
+            for vertex in 0..vertex_count {
+                for &kind in &kinds {
+                    write_one(kind, vertex, &mut bytes);
+                }
+            }
+        }
+    }
+    (bytes, descriptor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    fn layout(arrangement: Arrangement, normals: Option<NormalFormat>, colors: bool, pick_ids: bool) -> VertexLayout
+    {
+        VertexLayout { arrangement, normals, colors, pick_ids }
+    }
+
+    fn offsets(descriptor: &LayoutDescriptor) -> Vec<(&'static str, usize, usize)>
+    {
+        descriptor.attributes.iter().map(|a| (a.name, a.offset, a.stride)).collect()
+    }
+
+    #[test]
+    fn interleaved_and_planar_offsets()
+    {
+        let interleaved = describe(10, &layout(Arrangement::Interleaved, None, false, true));
+        assert_eq!(interleaved.stride, Some(16));
+        assert_eq!(offsets(&interleaved), vec![("position", 0, 16), ("pick_id", 12, 16)]);
+        let interleaved = describe(10, &layout(Arrangement::Interleaved, Some(NormalFormat::Snorm8x4), false, false));
+        assert_eq!(offsets(&interleaved), vec![("position", 0, 16), ("normal", 12, 16)]);
+        assert_eq!(interleaved.byte_length, 160);
+
+        let planar = describe(10, &layout(Arrangement::Planar, Some(NormalFormat::Unorm16x4), true, true));
+        assert_eq!(planar.stride, None);
+        assert_eq!(offsets(&planar), vec![("position", 0, 12), ("normal", 120, 8), ("color", 200, 12), ("pick_id", 320, 4)]);
+        assert_eq!(planar.byte_length, 360);
+    }
+
+    #[test]
+    fn quantized_normals()
+    {
+        // three vertices with normals (-1, 0, 1), and one out of range that is clamped
+        let positions = [0.0; 12];
+        let normals = [-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -2.0, 0.0, 2.0];
+        let normal_bytes = |format: NormalFormat| {
+            let (bytes, descriptor) = write(&positions, &normals, &[], &[], &layout(Arrangement::Planar, Some(format), false, false));
+            assert_eq!(descriptor.byte_length, bytes.len());
+            bytes[descriptor.attributes[1].offset..].to_vec()
+        };
+
+        // -127, 0, 127 and a zero padding byte
+        let snorm = normal_bytes(NormalFormat::Snorm8x4);
+        assert_eq!(snorm.len(), 16);
+        assert!(snorm.chunks(4).all(|normal| normal == [0x81, 0x00, 0x7f, 0x00]));
+
+        // 0, 32768 and 65535, little-endian, and a zero padding word
+        let unorm = normal_bytes(NormalFormat::Unorm16x4);
+        assert_eq!(unorm.len(), 32);
+        assert!(unorm.chunks(8).all(|normal| normal == [0x00, 0x00, 0x00, 0x80, 0xff, 0xff, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn interleaved_bytes()
+    {
+        let positions = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let colors = [0.5; 6];
+        let (bytes, descriptor) = write(&positions, &[], &colors, &[7, 8], &layout(Arrangement::Interleaved, None, true, true));
+        assert_eq!(descriptor.byte_length, bytes.len());
+        assert_eq!(bytes.len(), 2 * 28);
+        let f32_at = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        // second vertex: position, color, pick ID
+        assert_eq!((f32_at(28), f32_at(36), f32_at(40), u32_at(52)), (4.0, 6.0, 0.5, 8));
+    }
+}
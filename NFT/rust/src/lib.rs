@@ -5,17 +5,70 @@ use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 extern crate nalgebra_glm as glm;
 
+pub mod align;
+pub mod amino_acid;
+pub mod annotation;
+pub mod error;
+pub mod extrude;
+pub mod fasta;
+pub mod fastq;
+pub mod frame;
+pub mod genbank;
+pub mod genetic_code;
+pub mod hgvs;
+pub mod input;
+pub mod layout;
+pub mod matching;
+pub mod mesh;
+pub mod nucleotide;
+pub mod options;
+pub mod orf;
+pub mod packed;
+pub mod region;
+pub mod scoring;
+pub mod spline;
+#[cfg(test)]
+pub mod testing;
+pub mod transport;
+pub mod twobit;
+pub mod variant;
+pub mod vcf;
+
+use std::ops::Range;
+
+use align::Alignment;
+use amino_acid::{AminoAcid, Change, Residue};
+use annotation::Feature;
+use extrude::CrossSection;
+pub use error::{Error, Result};
+use frame::{Frame, FrameSelection, Strand};
+use genetic_code::GeneticCode;
+use hgvs::Mutation;
+use input::Input;
+use layout::{LayoutDescriptor, VertexLayout};
+use matching::OrfMatch;
+use mesh::Mesh;
+use nucleotide::Nucleotide;
+pub use options::Options;
+use orf::Orf;
+use variant::Variant;
+
 #[wasm_bindgen]
 extern {
     pub fn alert(s: &str);
 }
 
 // code to implement console_log
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
     fn log(s: &str);
 }
+
+// native builds (tools, tests) have no browser console
+#[cfg(not(target_arch = "wasm32"))]
+fn log(_s: &str) {}
 macro_rules! console_log {
     ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
 }
@@ -35,6 +88,7 @@ pub struct SimpleStrip {
     division: i32,
 }
 
+// directions are the tangents of the path at both ends, see spline::hermite
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ComplexStrip {
     start_position: [f32; 3],
@@ -53,7 +107,7 @@ pub struct ComplexStrip {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Vertex {
     position: glm::Vec3,
     normal: glm::Vec3,
@@ -75,11 +129,20 @@ pub fn points_to_quad(points: &[glm::Vec3], normals: &[glm::Vec3], colors: &[glm
     return vertices;
 }
 
+pub fn try_strip_to_vertices(points: Vec<glm::Vec3>, normals: Vec<glm::Vec3>, colors: Vec<glm::Vec3>) -> Result<Vec<Vertex>>
+{
+    if points.len() < 4 {
+        return Err(Error::TooFewPoints { count: points.len() });
+    }
+    Ok(strip_to_vertices(points, normals, colors))
+}
+
 pub fn strip_to_vertices(points: Vec<glm::Vec3>, normals: Vec<glm::Vec3>, colors: Vec<glm::Vec3>) -> Vec<Vertex>
 {
     let mut vertices: Vec<Vertex> = Vec::new();
 
-    let quad_count = (points.len() - 2) / 2;
+    // fewer than 4 points make no quad at all
+    let quad_count = points.len().saturating_sub(2) / 2;
     for i in 0 .. quad_count {
         let mut quad = points_to_quad(&points[2 * i .. 2 * (i + 2)], &normals[2 * i .. 2 * (i + 2)], &colors[2 * i .. 2 * (i + 2)]);
         vertices.append(&mut quad);
@@ -95,6 +158,46 @@ pub fn strip_to_vertices(points: Vec<glm::Vec3>, normals: Vec<glm::Vec3>, colors
 
 
 pub fn complex_strip(strip: ComplexStrip) -> Vec<Vertex>
+{
+    let (pts, normals, colors) = complex_strip_rows(&strip);
+    strip_to_vertices(pts, normals, colors)
+}
+
+// complex_strip with `section` swept along it, closed by a cap at its start and / or its end
+pub fn extruded_strip(strip: ComplexStrip, section: &CrossSection, caps: (bool, bool)) -> Vec<Vertex>
+{
+    let (pts, normals, colors) = complex_strip_rows(&strip);
+    let mut vertices: Vec<Vertex> = Vec::new();
+    if let Some((center, rim)) = extrude::cap(section, &pts, &normals, &colors, 0, false).filter(|_| caps.0) {
+        vertices.append(&mut extrude::fan_to_vertices(center, &rim));
+    }
+    for (face_pts, face_normals, face_colors) in extrude::sweep(section, &pts, &normals, &colors) {
+        vertices.append(&mut strip_to_vertices(face_pts, face_normals, face_colors));
+    }
+    if let Some((center, rim)) = extrude::cap(section, &pts, &normals, &colors, pts.len() / 2 - 1, true).filter(|_| caps.1) {
+        vertices.append(&mut extrude::fan_to_vertices(center, &rim));
+    }
+    vertices
+}
+
+// same as extruded_strip, but added to an indexed mesh: each face shares its first row with the same face of the
+// strip before
+pub fn extruded_strip_mesh(strip: ComplexStrip, section: &CrossSection, caps: (bool, bool), mesh: &mut Mesh)
+{
+    let (pts, normals, colors) = complex_strip_rows(&strip);
+    if let Some((center, rim)) = extrude::cap(section, &pts, &normals, &colors, 0, false).filter(|_| caps.0) {
+        mesh.append_fan(center, &rim);
+    }
+    for (lane, (face_pts, face_normals, face_colors)) in extrude::sweep(section, &pts, &normals, &colors).iter().enumerate() {
+        mesh.append_strip(lane, face_pts, face_normals, face_colors);
+    }
+    if let Some((center, rim)) = extrude::cap(section, &pts, &normals, &colors, pts.len() / 2 - 1, true).filter(|_| caps.1) {
+        mesh.append_fan(center, &rim);
+    }
+}
+
+// top and bottom points of each row of a complex strip, with their normals and colors
+fn complex_strip_rows(strip: &ComplexStrip) -> (Vec<glm::Vec3>, Vec<glm::Vec3>, Vec<glm::Vec3>)
 {
     let start_position = glm::vec3(strip.start_position[0], strip.start_position[1], strip.start_position[2]);
     let end_position = glm::vec3(strip.end_position[0], strip.end_position[1], strip.end_position[2]);
@@ -106,43 +209,35 @@ pub fn complex_strip(strip: ComplexStrip) -> Vec<Vertex>
     let end_color = glm::vec3(strip.end_color[0], strip.end_color[1], strip.end_color[2]);
 
 
-    let mut s_dir = start_direction;
-    if glm::length(&s_dir) < 0.001 {
-        s_dir = glm::normalize(&(&end_position - &start_position));
-    }
-    let s_tangent = glm::normalize(&glm::cross(&start_normal, &s_dir));
-    let s_top = start_position + 0.5 * strip.start_thickness * s_tangent;
-    let s_bot = start_position - 0.5 * strip.start_thickness * s_tangent;
-
-    let mut e_dir = end_direction;
-    if glm::length(&e_dir) < 0.001 {
-        e_dir = glm::normalize(&(&end_position - &start_position));
-    }
-    let e_tangent = glm::normalize(&glm::cross(&end_normal, &e_dir));
-    let e_top = end_position + 0.5 * strip.end_thickness * e_tangent;
-    let e_bot = end_position - 0.5 * strip.end_thickness * e_tangent;
+    // the directions only count for their heading, however short; a zero one follows the chord
+    let chord = transport::safe_normalize(&(end_position - start_position), &transport::perpendicular(&start_normal));
+    let s_dir = transport::safe_normalize(&start_direction, &chord);
+    let e_dir = transport::safe_normalize(&end_direction, &chord);
+
+    // the path is the cubic Hermite segment with the directions as its tangents; each row heads along it (or
+    // turns from s_dir to e_dir at a constant rate where it stalls), and its normal is carried over from the row
+    // before, then twisted a little more towards end_normal
+    let rows = strip.division.max(1);
+    let alphas: Vec<f32> = (0..rows + 1).map(|i| i as f32 / rows as f32).collect();
+    let tangents: Vec<glm::Vec3> = alphas.iter()
+        .map(|&alpha| {
+            let derivative = spline::hermite_derivative(&start_position, &start_direction, &end_position, &end_direction, alpha);
+            transport::safe_normalize(&derivative, &transport::slerp(&s_dir, &e_dir, alpha))
+        })
+        .collect();
+    let frame_normals = transport::frames(&tangents, &start_normal, &end_normal);
 
-    let ddir = s_dir - e_dir;
     let mut pts: Vec<glm::Vec3> = Vec::new();
     let mut normals: Vec<glm::Vec3> = Vec::new();
     let mut colors: Vec<glm::Vec3> = Vec::new();
-    for i in 0..(strip.division + 1) {
-
-        let alpha = (i as f32) / (strip.division as f32);
-        let mut top = glm::mix(&s_top, &e_top, alpha) + alpha * (1.0 - alpha) * ddir;
-        let mut bottom = glm::mix(&s_bot, &e_bot, alpha) + alpha * (1.0 - alpha) * ddir;
-        let middle = 0.5 * (top + bottom);
-        let tang = glm::normalize(&(&top - &bottom));
+    for (i, &alpha) in alphas.iter().enumerate() {
+        let middle = spline::hermite(&start_position, &start_direction, &end_position, &end_direction, alpha);
+        let normal = frame_normals[i];
+        let tang = transport::safe_normalize(&glm::cross(&normal, &tangents[i]), &transport::perpendicular(&normal));
 
         let thickness = (1.0 - alpha) * strip.start_thickness + alpha * strip.end_thickness;
-        top = middle + 0.5 * thickness * tang;
-        bottom = middle - 0.5 * thickness * tang;
-        
-        pts.push(top);
-        pts.push(bottom);
-
-        let direction = glm::mix(&s_dir, &e_dir, alpha);
-        let normal = glm::normalize(&glm::cross(&direction, &(&top - &bottom)));
+        pts.push(middle + 0.5 * thickness * tang);
+        pts.push(middle - 0.5 * thickness * tang);
 
         normals.push(normal);
         normals.push(normal);
@@ -152,9 +247,7 @@ pub fn complex_strip(strip: ComplexStrip) -> Vec<Vertex>
         colors.push(color);
     }
 
-    let vertices = strip_to_vertices(pts, normals, colors);
-
-    return vertices;
+    (pts, normals, colors)
 }
 
 
@@ -167,8 +260,8 @@ pub fn simple_strip(strip: SimpleStrip) -> Vec<Vertex>
     let mut normals: Vec<glm::Vec3> = Vec::new();
     let mut colors: Vec<glm::Vec3> = Vec::new();
 
-    let direction = glm::normalize(&(&strip.end_position - &strip.start_position));
-    let tangent = glm::normalize(&glm::cross(&strip.normal, &direction));
+    let direction = transport::safe_normalize(&(strip.end_position - strip.start_position), &transport::perpendicular(&strip.normal));
+    let tangent = transport::safe_normalize(&glm::cross(&strip.normal, &direction), &transport::perpendicular(&direction));
     let segment = glm::length(&(&strip.end_position - &strip.start_position)) / (strip.division as f32);
 
     let top: glm::Vec3 = strip.start_position + 0.5 * strip.thickness * tangent;
@@ -217,26 +310,75 @@ pub fn vertices_to_float(vertices: Vec<Vertex>) -> Vec<f32>
 
 
 
+// strips of a ring get distinct colors up to this many
+const COLOR_CYCLE: i32 = 20;
+
 pub fn fract_pow(val: f32, exp: i32) -> f32
 {
     let res: f32 = val.powf(exp as f32);
     return res - res.floor();
 }
 
-pub fn aa_to_strip(comb: Vec<i8>, comb_count: i32, comb_index: i32) -> Vec<Vertex>
+// where a strip starts: slot `index` of `count` around a ring, on ring `ring` of a stack of `ring_count` rings;
+// `offset` is a fraction of a slot past `index`, for strips squeezed in between two slots
+#[derive(Debug, Clone, Copy)]
+pub struct RingSlot {
+    pub index: i32,
+    pub offset: f32,
+    pub count: i32,
+    pub ring: i32,
+    pub ring_count: i32,
+}
+
+impl RingSlot {
+    // rings are stacked along z, centered on the origin
+    pub fn height(&self) -> f32
+    {
+        let ring_spacing: f32 = 1.5;
+        (self.ring as f32 - 0.5 * (self.ring_count - 1) as f32) * ring_spacing
+    }
+}
+
+// the vertices of one protein, with the record, reading frame and nucleotide range it was translated from
+pub struct Strip {
+    pub record: String,
+    /// Gene name of an annotated protein.
+    pub name: Option<String>,
+    pub frame: Frame,
+    pub start: usize,
+    pub end: usize,
+    /// Nucleotide range of the reference protein it was matched with, None for a novel protein.
+    pub reference: Option<Range<usize>>,
+    pub vertices: Vec<Vertex>,
+    /// Triangles of `vertices` when they are indexed (Options::indexed), else empty: `vertices` are triangles.
+    pub indices: Vec<u32>,
+    /// Index in `vertices` of the first vertex of each residue.
+    pub residue_vertices: Vec<usize>,
+    /// What each residue is, and the forward-strand range of its codon.
+    pub residues: Vec<(Residue, Range<usize>)>,
+    /// Changes from the reference protein; their `vertex` indexes `vertices`.
+    pub mutations: Vec<Mutation>,
+}
+
+pub fn aa_to_strip(comb: Vec<Residue>, slot: RingSlot, orf: &Orf, options: &Options) -> Strip
 {
+    let comb_count = slot.count;
+    let comb_index = slot.index;
     let mut vertices: Vec<Vertex> = Vec::new();
+    let mut mesh = Mesh::new();
 
-    let color_a = glm::vec3(fract_pow(1.2135, comb_index), fract_pow(1.8214, comb_index), fract_pow(1.5435, comb_index));
+    // the powers lose their fractional part, then overflow, past a few dozen strips: colors repeat instead
+    let color_index = comb_index % COLOR_CYCLE;
+    let color_a = glm::vec3(fract_pow(1.2135, color_index), fract_pow(1.8214, color_index), fract_pow(1.5435, color_index));
     let color_b = glm::vec3(0.1, 0.8, 1.0);
     let mut count = 0;
     let total_count = comb.len() as f32;
 
     let radius: f32 = 2.0;
     let separation: f32 = 2.0 * 3.14159265 / comb_count as f32;
-    let angle: f32 = comb_index as f32 * separation;
+    let angle: f32 = (comb_index as f32 + slot.offset) * separation;
     let normal: glm::Vec3 = glm::vec3(angle.cos(), angle.sin(), 0.0);
-    let pos: glm::Vec3 = radius * normal;
+    let pos: glm::Vec3 = radius * normal + glm::vec3(0.0, 0.0, slot.height());
 
     let mut scale = 0.3;
     let mut thick_scale = 1.0;
@@ -246,8 +388,20 @@ pub fn aa_to_strip(comb: Vec<i8>, comb_count: i32, comb_index: i32) -> Vec<Verte
     let mut s_nor = glm::vec3(0.0, 0.0, 1.0);
     let mut s_col = glm::mix(&color_a, &color_b, 0.0);
 
-    for _aa in comb {
-        let mut aa = 1 * _aa;
+    // twists of the residues: a quarter and an eighth of a turn, and that of a normal mixed with half its side
+    let (quarter, eighth, slight) = (std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_4, 0.5_f32.atan());
+
+    let mut residue_vertices: Vec<usize> = Vec::new();
+    let residues: Vec<(Residue, Range<usize>)> = comb.iter().enumerate()
+        .map(|(i, &residue)| (residue, orf.codon_range(i).unwrap_or(orf.start..orf.start)))
+        .collect();
+    // stop codons are not drawn; a cap closes a run of residues of the same extruded shape at both ends
+    let sections: Vec<Option<&CrossSection>> = comb.iter()
+        .map(|residue| Some(options.cross_sections.of(residue.amino_acid)).filter(|_| residue.amino_acid != AminoAcid::Stop))
+        .collect();
+    // the segment of each residue, None if it is not drawn; its directions are headings until the path is fit
+    let mut segments: Vec<Option<ComplexStrip>> = Vec::new();
+    for residue in comb {
         count = count + 1;
         let alpha: f32 = (count as f32) / (total_count - 1.0);
         let old_dir = 1.0 * s_dir;
@@ -260,189 +414,213 @@ pub fn aa_to_strip(comb: Vec<i8>, comb_count: i32, comb_index: i32) -> Vec<Verte
 
         let mut e_pos: glm::Vec3 = glm::vec3(0.0, 0.0, 0.0); 
         let mut e_dir: glm::Vec3 = glm::vec3(0.0, 0.0, 0.0);
-        let mut e_nor: glm::Vec3 = glm::vec3(0.0, 0.0, 0.0);
+        // turn of the normal around e_dir, on top of the frame carried along the path
+        let mut twist: f32 = 0.0;
         let mut e_col: glm::Vec3 = glm::mix(&color_a, &color_b, alpha);
         let mut e_thick = 0.05;
         let mut div: i32 = 2;
         let mut ok: bool = true;
-        let mut changed: bool = false;
-        if aa < 0 {
-            changed = true;
-            aa = -aa;
-        }
 
-        if aa == 0 {
-            e_pos = s_pos + scale * old_dir;
-            e_dir = old_dir;
-            e_nor = glm::normalize(&(old_nor + old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 2;
-        }
-        else if aa == 1 {
-            e_pos = s_pos + scale * old_dir;
-            e_dir = old_dir;
-            e_nor = glm::normalize(&(old_nor - old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 2;
-        }
-        else if aa == 2 {
-            e_pos = s_pos + scale * old_dir;
-            e_dir = old_dir;
-            e_nor = old_tan;
-            e_thick = thick_scale * old_thick;
-            div = 2;
-        }
-        else if aa == 3 {
-            e_pos = s_pos + scale * old_dir;
-            e_dir = old_dir;
-            e_nor = -old_tan;
-            e_thick = thick_scale * old_thick;
-            div = 2;
-        }
+        match residue.amino_acid {
+            AminoAcid::Phe => {
+                e_pos = s_pos + scale * old_dir;
+                e_dir = old_dir;
+                twist = eighth;
+                e_thick = thick_scale * old_thick;
+                div = 2;
+            }
+            AminoAcid::Leu => {
+                e_pos = s_pos + scale * old_dir;
+                e_dir = old_dir;
+                twist = -eighth;
+                e_thick = thick_scale * old_thick;
+                div = 2;
+            }
+            AminoAcid::Ser => {
+                e_pos = s_pos + scale * old_dir;
+                e_dir = old_dir;
+                twist = quarter;
+                e_thick = thick_scale * old_thick;
+                div = 2;
+            }
+            AminoAcid::Tyr => {
+                e_pos = s_pos + scale * old_dir;
+                e_dir = old_dir;
+                twist = -quarter;
+                e_thick = thick_scale * old_thick;
+                div = 2;
+            }
 
-        else if aa == 4 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
-            e_dir = old_nor;
-            e_nor = glm::normalize(&(-old_dir + 0.5 * old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 5 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
-            e_dir = old_nor;
-            e_nor = glm::normalize(&(-old_dir - 0.5 * old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 6 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
-            e_dir = old_nor;
-            e_nor = glm::normalize(&(-old_dir + old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 7 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
-            e_dir = old_nor;
-            e_nor = glm::normalize(&(-old_dir - old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
+            AminoAcid::Cys => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
+                e_dir = old_nor;
+                twist = slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Trp => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
+                e_dir = old_nor;
+                twist = -slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Pro => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
+                e_dir = old_nor;
+                twist = eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::His => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_nor);
+                e_dir = old_nor;
+                twist = -eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
 
-        else if aa == 8 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
-            e_dir = -old_nor;
-            e_nor = glm::normalize(&(old_dir - 0.5 * old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 9 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
-            e_dir = -old_nor;
-            e_nor = glm::normalize(&(old_dir + 0.5 * old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 10 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
-            e_dir = -old_nor;
-            e_nor = glm::normalize(&(old_dir - old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 11 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
-            e_dir = -old_nor;
-            e_nor = glm::normalize(&(old_dir + old_tan));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
+            AminoAcid::Gln => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
+                e_dir = -old_nor;
+                twist = -slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Arg => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
+                e_dir = -old_nor;
+                twist = slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Ile => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
+                e_dir = -old_nor;
+                twist = -eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Met => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_nor);
+                e_dir = -old_nor;
+                twist = eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
 
-        else if aa == 12 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
-            e_dir = old_tan;
-            e_nor = glm::normalize(&(old_nor + 0.5 * old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 13 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
-            e_dir = old_tan;
-            e_nor = glm::normalize(&(old_nor - 0.5 * old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 14 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
-            e_dir = old_tan;
-            e_nor = glm::normalize(&(old_nor + old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 15 {
-            e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
-            e_dir = old_tan;
-            e_nor = glm::normalize(&(old_nor - old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
+            AminoAcid::Thr => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
+                e_dir = old_tan;
+                twist = -slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Asn => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
+                e_dir = old_tan;
+                twist = slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Lys => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
+                e_dir = old_tan;
+                twist = -eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Val => {
+                e_pos = s_pos + scale * (0.5 * old_dir + 0.5 * old_tan);
+                e_dir = old_tan;
+                twist = eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
 
-        else if aa == 16 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
-            e_dir = -old_tan;
-            e_nor = glm::normalize(&(old_nor + 0.5 * old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 17 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
-            e_dir = -old_tan;
-            e_nor = glm::normalize(&(old_nor - 0.5 * old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 18 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
-            e_dir = -old_tan;
-            e_nor = glm::normalize(&(old_nor + old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
-        else if aa == 19 {
-            e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
-            e_dir = -old_tan;
-            e_nor = glm::normalize(&(old_nor - old_dir));
-            e_thick = thick_scale * old_thick;
-            div = 6;
-        }
+            AminoAcid::Ala => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
+                e_dir = -old_tan;
+                twist = slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Asp => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
+                e_dir = -old_tan;
+                twist = -slight;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Glu => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
+                e_dir = -old_tan;
+                twist = eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
+            AminoAcid::Gly => {
+                e_pos = s_pos + scale * (0.5 * old_dir - 0.5 * old_tan);
+                e_dir = -old_tan;
+                twist = -eighth;
+                e_thick = thick_scale * old_thick;
+                div = 6;
+            }
 
 
-        else {
-            ok = false;
+            AminoAcid::Unknown => {
+                // unresolved residue: keep straight and flat, drawn in grey below
+                e_pos = s_pos + scale * old_dir;
+                e_dir = old_dir;
+                e_thick = thick_scale * old_thick;
+                e_col = glm::vec3(0.5, 0.5, 0.5);
+                div = 2;
+            }
+
+
+            _ => {
+                ok = false;
+            }
         }
 
-        if changed {
-            e_col = glm::vec3(1.0, 0.0, 0.0);
+        // the normal carried over from old_dir to e_dir without spinning, then twisted by the residue
+        let e_nor = transport::twist(&transport::transport(&old_nor, &old_dir, &e_dir), &e_dir, twist);
+
+        match residue.change {
+            Change::Substitution => e_col = glm::vec3(1.0, 0.0, 0.0),
+            Change::Insertion => e_col = glm::vec3(1.0, 0.55, 0.0),
+            Change::Unaligned => e_col = glm::vec3(0.5, 0.1, 0.1),
+            Change::Unchanged => {}
+        }
+        if residue.deleted > 0 {
+            // reference residues are missing right before this one
+            e_col = glm::vec3(0.8, 0.0, 0.8);
+        }
+        if let Some(color) = options.uncertain_color.filter(|_| residue.is_uncertain()) {
+            // a difference here may be a sequencing error rather than a mutation
+            e_col = glm::vec3(color[0], color[1], color[2]);
+        }
+        if let Some(color) = residue.effect.and_then(|effect| options.effect_colors.of(effect)) {
+            e_col = glm::vec3(color[0], color[1], color[2]);
         }
 
         if ok
         {
-            let strip: ComplexStrip = ComplexStrip {
+            segments.push(Some(ComplexStrip {
                 start_position: [s_pos.x, s_pos.y, s_pos.z],
                 start_normal: [s_nor.x, s_nor.y, s_nor.z],
-                start_direction: [0.5 * scale * s_dir.x, 0.5 * scale * s_dir.y, 0.5 * scale * s_dir.z],
+                start_direction: [s_dir.x, s_dir.y, s_dir.z],
                 start_color: [s_col.x, s_col.y, s_col.z],
     
                 end_position: [e_pos.x, e_pos.y, e_pos.z],
                 end_normal: [e_nor.x, e_nor.y, e_nor.z],
-                end_direction: [0.5 * scale * e_dir.x, 0.5 * scale * e_dir.y, 0.5 * scale * e_dir.z],
+                end_direction: [e_dir.x, e_dir.y, e_dir.z],
                 end_color: [e_col.x, e_col.y, e_col.z],
     
                 start_thickness: s_thick,
                 end_thickness: e_thick,
-                division: div,
-            };
-            vertices.append(& mut complex_strip(strip));
+                division: options.path.rows(div),
+            }));
 
             s_pos = e_pos;
             s_dir = e_dir;
@@ -450,178 +628,968 @@ pub fn aa_to_strip(comb: Vec<i8>, comb_count: i32, comb_index: i32) -> Vec<Verte
             s_col = e_col;
             s_thick = e_thick;
         }
+        else {
+            segments.push(None);
+        }
     }
 
-    return vertices;
+    // a spline through the ends of the drawn residues: both segments at a point share its tangent
+    let drawn: Vec<&ComplexStrip> = segments.iter().flatten().collect();
+    let vec3 = |v: [f32; 3]| glm::vec3(v[0], v[1], v[2]);
+    let knots: Vec<glm::Vec3> = drawn.first().map(|first| vec3(first.start_position)).into_iter()
+        .chain(drawn.iter().map(|segment| vec3(segment.end_position)))
+        .collect();
+    let headings: Vec<glm::Vec3> = drawn.first().map(|first| vec3(first.start_direction)).into_iter()
+        .chain(drawn.iter().map(|segment| vec3(segment.end_direction)))
+        .collect();
+    let tangents = spline::tangents(&knots, &headings, &options.path);
+    for (k, segment) in segments.iter_mut().flatten().enumerate() {
+        segment.start_direction = [tangents[k].x, tangents[k].y, tangents[k].z];
+        segment.end_direction = [tangents[k + 1].x, tangents[k + 1].y, tangents[k + 1].z];
+    }
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        residue_vertices.push(if options.indexed { mesh.vertices.len() } else { vertices.len() });
+        if let Some(strip) = segment {
+            let section = options.cross_sections.of(residues[i].0.amino_acid);
+            let caps = (i == 0 || sections[i - 1] != Some(section), sections.get(i + 1).copied().flatten() != Some(section));
+            if options.indexed {
+                extruded_strip_mesh(strip, section, caps, &mut mesh);
+            }
+            else {
+                vertices.append(& mut extruded_strip(strip, section, caps));
+            }
+        }
+    }
+
+    if options.indexed {
+        vertices = mesh.vertices;
+    }
+    Strip {
+        record: orf.record.clone(),
+        name: orf.name.clone(),
+        frame: orf.frame,
+        start: orf.start,
+        end: orf.end,
+        reference: None,
+        vertices,
+        indices: mesh.indices,
+        residue_vertices,
+        residues,
+        mutations: Vec::new(),
+    }
 }
 
 
 
-pub fn dna_to_aa(seq: &str) -> Vec<Vec<i8>>
+// proteins of the ORFs in frame +1, see orf::find_orfs
+pub fn dna_to_aa(seq: &str, code: &GeneticCode, alternative_starts: bool) -> Vec<Vec<AminoAcid>>
 {
-    let mut comb_ready: bool = false;
-    let mut enc: Vec<Vec<i8>> = Vec::new();
-    let mut cur_comb: Vec<i8> = Vec::new();
+    let options = Options { alternative_starts, ..Options::default() };
+    let records = input::parse(seq).map(|input| input.records).unwrap_or_default();
+    records.iter()
+        .flat_map(|record| orf::find_orfs(&record.id, &record.sequence, Frame::FORWARD_1, code, &options))
+        .map(|orf| orf.protein)
+        .collect()
+}
 
-    let mut start_pos: usize = 0;
-    let mut end_pos: usize = 0;
-    let mut prot_count: i32 = 0;
+// ORFs of the frames selected in the options
+pub fn record_to_aa(record: &fasta::Record, code: &GeneticCode, options: &Options) -> Vec<Orf>
+{
+    match options.frames {
+        FrameSelection::Single(frame) => orf::find_orfs_in_frames(&record.id, &record.sequence, &[frame], code, options),
+        FrameSelection::All => orf::find_orfs_in_frames(&record.id, &record.sequence, &Frame::ALL, code, options),
+        FrameSelection::Best => {
+            // the frame with the most residues in ORFs; ties go to the first frame
+            let mut best: Vec<Orf> = Vec::new();
+            let mut best_len = 0;
+            for frame in Frame::ALL.iter() {
+                let orfs = orf::find_orfs(&record.id, &record.sequence, *frame, code, options);
+                let len: usize = orfs.iter().map(|orf| orf.protein.len()).sum();
+                if len > best_len {
+                    best = orfs;
+                    best_len = len;
+                }
+            }
+            best
+        }
+    }
+}
 
-    let mut it = seq.chars();
-    let mut count = 0;
-    let mut triple = ['a', 'a', 'a'];
-    for i in 0..seq.len() {
+pub fn records_to_aa(records: &[fasta::Record], code: &GeneticCode, options: &Options) -> Vec<Orf>
+{
+    records.iter().flat_map(|record| record_to_aa(record, code, options)).collect()
+}
 
-        let m = it.next().unwrap();
-        if m != '\n' && m != '\r'
-        {
-            triple[count] = m;
-            count += 1;
+fn check_alphabet(seq: &str) -> Result<()>
+{
+    for (position, character) in seq.chars().enumerate() {
+        if !character.is_whitespace() && Nucleotide::from_char(character).is_none() {
+            return Err(Error::InvalidCharacter { record: String::new(), position, character });
         }
+    }
+    Ok(())
+}
 
-        if count == 3
-        {
-//            console_log!("{:?}", triple);
-            count = 0;
-
-            let m = triple[0];
-            let n = triple[1];
-            let o = triple[2];
-
-            let mut codon: i8 = 0;
-            if m == 't' {
-                if n == 't' {
-                    if o == 't' || o == 'c' {codon = 0;}
-                    else if o == 'a' || o == 'g' {codon = 1;}
-                }
-                else if n == 'c' {codon = 2;}
-                else if n == 'a' {
-                    if o == 't' || o == 'c' {codon = 3;}
-                    else if o == 'a' || o == 'g' {codon = 127;}
-                }
-                else if n == 'g' {
-                    if o == 't' || o == 'c' {codon = 4;}
-                    else if o == 'g' {codon = 5;}
-                    else if o == 'a' {codon = 127;}
+// same as dna_to_aa, but rejects characters outside the IUPAC alphabet instead of reading them as N
+pub fn try_dna_to_aa(seq: &str, code: &GeneticCode, alternative_starts: bool) -> Result<Vec<Vec<AminoAcid>>>
+{
+    for record in input::parse(seq)?.records {
+        check_alphabet(&record.sequence).map_err(|e| e.in_record(&record.id))?;
+    }
+    Ok(dna_to_aa(seq, code, alternative_starts))
+}
+
+pub fn try_records_to_aa(records: &[fasta::Record], code: &GeneticCode, options: &Options) -> Result<Vec<Orf>>
+{
+    for record in records {
+        check_alphabet(&record.sequence).map_err(|e| e.in_record(&record.id))?;
+    }
+    Ok(records_to_aa(records, code, options))
+}
+
+// translate a complete coding sequence codon by codon, stop codons included
+pub fn try_translate(seq: &str, code: &GeneticCode) -> Result<Vec<AminoAcid>>
+{
+    check_alphabet(seq)?;
+
+    let bases: Vec<Nucleotide> = seq.chars().filter_map(Nucleotide::from_char).collect();
+    if !bases.len().is_multiple_of(3) {
+        return Err(Error::PartialCodon { record: String::new(), position: bases.len() - bases.len() % 3 });
+    }
+    Ok(bases.chunks(3).map(|c| code.translate([c[0], c[1], c[2]])).collect())
+}
+
+// annotate each residue of an ORF from its alignment to the reference protein; residues the
+// alignment does not cover stay Unaligned
+pub fn compare_aa(orf: &Orf, reference: &[AminoAcid], alignment: &Alignment) -> Vec<Residue>
+{
+    let mut result: Vec<Residue> = orf.protein.iter().zip(orf.codons.iter())
+        .map(|(&amino_acid, &codon)| Residue { amino_acid, codon, reference: None, change: Change::Unaligned, deleted: 0, effect: None })
+        .collect();
+
+    let mut deleted = 0;
+    for column in &alignment.columns {
+        match (column.sample, column.reference) {
+            (Some(s), Some(r)) => {
+                let residue = &mut result[s];
+                residue.reference = Some(reference[r]);
+                // an unknown residue is uncertain, not a mutation
+                residue.change = if reference[r] == residue.amino_acid || residue.amino_acid == AminoAcid::Unknown {
+                    Change::Unchanged
                 }
+                else {
+                    Change::Substitution
+                };
+                residue.deleted = deleted;
+                deleted = 0;
             }
-            else if m == 'c' {
-                if n == 't' {codon = 1;}
-                else if n == 'c' {codon = 6;}
-                else if n == 'a' {
-                    if o == 't' || o == 'c' {codon = 7;}
-                    else if o == 'a' || o == 'g' {codon = 8;}
-                }
-                else if n == 'g' {codon = 9;}
+            (Some(s), None) => {
+                result[s].change = Change::Insertion;
+                result[s].deleted = deleted;
+                deleted = 0;
             }
-            else if m == 'a' {
-                if n == 't' {
-                    if o == 'g' {codon = 11;}
-                    else {codon = 10;}
-                }
-                else if n == 'c' {codon = 12;}
-                else if n == 'a' {
-                    if o == 't' || o == 'c' {codon = 13;}
-                    else if o == 'a' || o == 'g' {codon = 14;}
-                }
-                else if n == 'g' {
-                    if o == 't' || o == 'c' {codon = 2;}
-                    else if o == 'a' || o == 'g' {codon = 9;}
-                }
+            (None, Some(_)) => deleted += 1,
+            (None, None) => {}
+        }
+    }
+    if let Some(last) = result.last_mut() {
+        last.deleted += deleted;
+    }
+    result
+}
+
+// residues of a sample ORF whose codons hold the bases a variant brought in, none if the variant was called
+// from another sample record
+fn residues_hit(variant: &Variant, orf: &Orf, count: usize) -> Vec<usize>
+{
+    if variant.sample_record != orf.record {
+        return Vec::new();
+    }
+    // a deletion leaves no base behind: take the codon of the base after it
+    let range = variant.sample_range();
+    let last = range.end.max(range.start + 1);
+    let mut residues: Vec<usize> = (range.start..last)
+        .filter_map(|position| orf.coding_offset(position))
+        // the stop codon has no residue of its own
+        .map(|offset| (offset / 3).min(count.saturating_sub(1)))
+        .collect();
+    residues.sort_unstable();
+    residues.dedup();
+    residues
+}
+
+// give each residue of a sample ORF the most severe effect of the variants in its codon, as found on the
+// reference ORF it was matched with
+pub fn mark_effects(comb: &mut [Residue], orf: &Orf, reference: &Orf, variants: &[Variant])
+{
+    if comb.is_empty() {
+        return;
+    }
+    for variant in variants {
+        let effect = match variant.effect_on(reference) {
+            Some(effect) => effect,
+            None => continue,
+        };
+        for residue in residues_hit(variant, orf, comb.len()) {
+            comb[residue].effect = comb[residue].effect.max(Some(effect));
+        }
+    }
+}
+
+// the HGVS labels of the variants that hit a sample ORF, drawn on the first residue each one hits
+pub fn mutations(strip: &Strip, orf: &Orf, reference: &Orf, variants: &[Variant]) -> Vec<Mutation>
+{
+    let mut mutations: Vec<Mutation> = Vec::new();
+    for variant in variants {
+        let effect = match variant.effect_for(reference) {
+            Some(effect) => effect,
+            None => continue,
+        };
+        if let Some(&residue) = residues_hit(variant, orf, orf.protein.len()).first() {
+            // "S:D614G" for an annotated gene
+            let label = hgvs::label(effect, &reference.protein);
+            mutations.push(Mutation {
+                hgvs: hgvs::hgvs(effect, &reference.protein),
+                label: match &reference.name {
+                    Some(name) => format!("{}:{}", name, label),
+                    None => label,
+                },
+                effect: effect.effect,
+                residue,
+                vertex: strip.residue_vertices.get(residue).copied().unwrap_or(0),
+            });
+        }
+    }
+    mutations
+}
+
+
+// which ring a protein is drawn on: one ring per frame when all six frames are shown
+fn ring_of(orf: &Orf, frames: FrameSelection) -> usize
+{
+    match frames {
+        FrameSelection::All => orf.frame.index(),
+        _ => 0,
+    }
+}
+
+// slots of the sample proteins of a ring. Matched proteins take the slot of their reference, so they keep their
+// angle whatever appears or disappears around them; novel proteins share the gap after the previous matched one
+fn ring_slots(matches: &[OrfMatch], sample_count: usize, reference_count: usize, ring: usize, ring_count: usize) -> Vec<RingSlot>
+{
+    let slot = |index: i32, offset: f32, count: usize| RingSlot {
+        index,
+        offset,
+        count: count as i32,
+        ring: ring as i32,
+        ring_count: ring_count as i32,
+    };
+    if reference_count == 0 {
+        return (0..sample_count).map(|s| slot(s as i32, 0.0, sample_count)).collect();
+    }
+
+    let mut reference_of: Vec<Option<usize>> = vec![None; sample_count];
+    for m in matches {
+        if let (Some(s), Some(r)) = (m.sample, m.reference) {
+            reference_of[s] = Some(r);
+        }
+    }
+
+    let mut slots: Vec<RingSlot> = Vec::new();
+    // slot of the last matched protein; before the first one, novel proteins go just before slot 0
+    let mut anchor: i32 = -1;
+    let mut s = 0;
+    while s < sample_count {
+        if let Some(r) = reference_of[s] {
+            anchor = r as i32;
+            slots.push(slot(anchor, 0.0, reference_count));
+            s += 1;
+            continue;
+        }
+        let run_end = (s..sample_count).find(|&t| reference_of[t].is_some()).unwrap_or(sample_count);
+        let run = run_end - s;
+        for k in 0..run {
+            slots.push(slot(anchor, (k + 1) as f32 / (run + 1) as f32, reference_count));
+        }
+        s = run_end;
+    }
+    slots
+}
+
+// match sample proteins to the reference proteins of the same ring and build the strips of the sample;
+// also returns the reference proteins left without a match
+pub fn proteins_to_strips(enc: &[Orf], ref_enc: &[Orf], variants: &[Variant], options: &Options) -> (Vec<Strip>, Vec<Orf>)
+{
+    let frames = options.frames;
+    let scoring = options.scoring();
+    let ring_count = match frames {
+        FrameSelection::All => Frame::ALL.len(),
+        _ => 1,
+    };
+
+    let mut strips: Vec<Strip> = Vec::new();
+    let mut lost: Vec<Orf> = Vec::new();
+    for ring in 0..ring_count {
+        let orfs: Vec<&Orf> = enc.iter().filter(|orf| ring_of(orf, frames) == ring).collect();
+        let references: Vec<&Orf> = ref_enc.iter().filter(|orf| ring_of(orf, frames) == ring).collect();
+
+        let matches = matching::match_orfs(&orfs, &references, options.alignment, &scoring);
+        let slots = ring_slots(&matches, orfs.len(), references.len(), ring, ring_count);
+
+        let mut by_sample: Vec<Option<&OrfMatch>> = vec![None; orfs.len()];
+        for m in &matches {
+            match (m.sample, m.reference) {
+                (Some(s), _) => by_sample[s] = Some(m),
+                (None, Some(r)) => lost.push(references[r].clone()),
+                (None, None) => {}
             }
-            else if m == 'g' {
-                if n == 't' {codon = 15;}
-                else if n == 'c' {codon = 16;}
-                else if n == 'a' {
-                    if o == 't' || o == 'c' {codon = 17;}
-                    else if o == 'a' || o == 'g' {codon = 18;}
-                }
-                else if n == 'g' {codon = 19;}
-            }
-
-            if comb_ready {
-                if codon == 127 {
-                    if cur_comb.len() > 0 {
-                        end_pos = i;
-                        console_log!("protein {} - from {} to {}", prot_count, start_pos, end_pos);
-//                        console_log!("{:?}", cur_comb);
-                        enc.push(cur_comb);
-                        cur_comb = Vec::new();
-                        prot_count += 1;
-                    }
-                    comb_ready = false;
-                }
-                else {
-                    cur_comb.push(codon);
-                }
+        }
+
+        for (i, orf) in orfs.iter().enumerate() {
+            // the alignment with the matched reference determines what should be red
+            let m = by_sample[i].expect("every sample protein is in a match");
+            let reference: &[AminoAcid] = match m.reference {
+                Some(r) => &references[r].protein,
+                None => &[],
+            };
+            let mut comb = compare_aa(orf, reference, &m.alignment);
+            if let Some(r) = m.reference {
+                mark_effects(&mut comb, orf, references[r], variants);
             }
-            else if codon == 11 {
-                comb_ready = true;
-                start_pos = i;
+
+            // convert each amino acid list into a strip of vertices
+            let mut strip = aa_to_strip(comb, slots[i], orf, options);
+            if let Some(r) = m.reference {
+                strip.reference = Some(references[r].start..references[r].end);
+                strip.mutations = mutations(&strip, orf, references[r], variants);
             }
+            strips.push(strip);
         }
     }
+    (strips, lost)
+}
 
-//    console_log!("{:?}", enc);
-    console_log!("parts count: {}", enc.len());
+// what a strip of the output buffer is: record, strand, frame and nucleotide range of its ORF, and its range of vertices
+#[derive(Debug, Clone, Serialize)]
+pub struct StripInfo {
+    pub record: String,
+    /// Gene name of an annotated protein, null for an ORF found by the finder.
+    pub name: Option<String>,
+    pub strand: Strand,
+    pub frame: Frame,
+    pub start: usize,
+    pub end: usize,
+    /// Nucleotide range of the matched reference protein, null for a novel protein.
+    pub reference: Option<Range<usize>>,
+    pub first_vertex: usize,
+    pub vertex_count: usize,
+    /// Range of the strip in the index buffer, 0 and 0 when the mesh is not indexed.
+    pub first_index: usize,
+    pub index_count: usize,
+    /// HGVS labels of the changes from the reference protein, with the first vertex of the residue each is on.
+    pub mutations: Vec<Mutation>,
+}
 
-    return enc;
+// what is under the mouse: the entry of Visualization::picks for the pick ID of a vertex
+#[derive(Debug, Clone, Serialize)]
+pub struct Pick {
+    /// Index of the strip (ORF) in Visualization::strips.
+    pub strip: usize,
+    /// Index of the residue in the sample protein.
+    pub residue: usize,
+    /// Forward-strand range of the codon.
+    pub start: usize,
+    pub end: usize,
+    pub codon: String,
+    pub sample: AminoAcid,
+    /// Reference residue aligned with it, null if none.
+    pub reference: Option<AminoAcid>,
+    pub change: Change,
+    pub effect: Option<variant::Effect>,
+    pub uncertain: bool,
 }
 
-pub fn compare_aa(data: Vec<i8>, reference: Vec<i8>) -> Vec<i8>
-{
-    let ref_len = reference.len();
-    let mut result: Vec<i8> = Vec::new();
-    for i in 0..data.len() {
-        let val = data[i].clone();
-        let mut ref_val: i8 = -1;
-        if i < ref_len {ref_val = reference[i].clone();}
+// a reference protein with no match in the sample
+#[derive(Debug, Clone, Serialize)]
+pub struct LostOrf {
+    pub record: String,
+    pub name: Option<String>,
+    pub strand: Strand,
+    pub frame: Frame,
+    pub start: usize,
+    pub end: usize,
+}
 
-        if val != ref_val {
-            result.push(-val);
+// the output of a load: the vertex buffer (positions, then normals, then colors) and the strips it holds
+#[wasm_bindgen]
+pub struct Visualization {
+    vertices: Vec<f32>,
+    /// Three vertices per triangle when the mesh is indexed, else empty.
+    indices: Vec<u32>,
+    /// Layout of buffer(), from the load options.
+    layout: VertexLayout,
+    /// Pick ID of each vertex: 0 for none, else 1 + index in `picks`. In an indexed mesh the first row of a residue
+    /// is the last row of the residue before, and has its ID; triangle_pick_ids tells each triangle's residue.
+    pick_ids: Vec<u32>,
+    picks: Vec<Pick>,
+    strips: Vec<StripInfo>,
+    lost: Vec<LostOrf>,
+    variants: Vec<Variant>,
+}
+
+impl Visualization {
+    pub fn new(strips: Vec<Strip>, lost: &[Orf], variants: Vec<Variant>) -> Visualization
+    {
+        let mut infos: Vec<StripInfo> = Vec::new();
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut pick_ids: Vec<u32> = Vec::new();
+        let mut picks: Vec<Pick> = Vec::new();
+        for mut strip in strips {
+            let first_vertex = vertices.len();
+            for mutation in &mut strip.mutations {
+                mutation.vertex += first_vertex;
+            }
+            // every vertex from the first one of a residue to the first one of the next belongs to it
+            let ends = strip.residue_vertices.iter().skip(1).copied().chain(std::iter::once(strip.vertices.len()));
+            for (i, ((residue, codon), (start, end))) in strip.residues.iter().zip(strip.residue_vertices.iter().copied().zip(ends)).enumerate() {
+                picks.push(Pick {
+                    strip: infos.len(),
+                    residue: i,
+                    start: codon.start,
+                    end: codon.end,
+                    codon: residue.codon.iter().map(|base| base.to_char()).collect(),
+                    sample: residue.amino_acid,
+                    reference: residue.reference,
+                    change: residue.change,
+                    effect: residue.effect,
+                    uncertain: residue.is_uncertain(),
+                });
+                pick_ids.extend(std::iter::repeat_n(picks.len() as u32, end - start));
+            }
+            infos.push(StripInfo {
+                record: strip.record,
+                name: strip.name,
+                strand: strip.frame.strand,
+                frame: strip.frame,
+                start: strip.start,
+                end: strip.end,
+                reference: strip.reference,
+                first_vertex,
+                vertex_count: strip.vertices.len(),
+                first_index: indices.len(),
+                index_count: strip.indices.len(),
+                mutations: strip.mutations,
+            });
+            indices.extend(strip.indices.iter().map(|&index| index + first_vertex as u32));
+            vertices.append(&mut strip.vertices);
+        }
+        pick_ids.resize(vertices.len(), 0);
+        Visualization {
+            vertices: vertices_to_float(vertices),
+            indices,
+            layout: VertexLayout::default(),
+            pick_ids,
+            picks,
+            strips: infos,
+            lost: lost.iter()
+                .map(|orf| LostOrf { record: orf.record.clone(), name: orf.name.clone(), strand: orf.strand(), frame: orf.frame, start: orf.start, end: orf.end })
+                .collect(),
+            variants,
+        }
+    }
+
+    pub fn strip_infos(&self) -> &[StripInfo]
+    {
+        &self.strips
+    }
+
+    pub fn lost_orfs(&self) -> &[LostOrf]
+    {
+        &self.lost
+    }
+
+    pub fn called_variants(&self) -> &[Variant]
+    {
+        &self.variants
+    }
+
+    pub fn pick_table(&self) -> &[Pick]
+    {
+        &self.picks
+    }
+
+    // positions, normals and colors of vertices()
+    fn planar(&self) -> (&[f32], &[f32], &[f32])
+    {
+        let size = self.vertices.len() / 3;
+        (&self.vertices[..size], &self.vertices[size..2 * size], &self.vertices[2 * size..])
+    }
+
+    /// The vertices in the layout of the load options, and where each attribute is in it.
+    pub fn vertex_buffer(&self) -> (Vec<u8>, LayoutDescriptor)
+    {
+        let (positions, normals, colors) = self.planar();
+        layout::write(positions, normals, colors, &self.pick_ids, &self.layout)
+    }
+}
+
+#[wasm_bindgen]
+impl Visualization {
+    // positions, then normals, then colors, laid out as in load_sequence
+    pub fn vertices(&self) -> Vec<f32>
+    {
+        self.vertices.clone()
+    }
+
+    // the three parts of vertices(), as separate arrays
+    pub fn positions(&self) -> Vec<f32>
+    {
+        self.planar().0.to_vec()
+    }
+
+    pub fn normals(&self) -> Vec<f32>
+    {
+        self.planar().1.to_vec()
+    }
+
+    pub fn colors(&self) -> Vec<f32>
+    {
+        self.planar().2.to_vec()
+    }
+
+    // the vertices laid out as the `layout` load option says (planar or interleaved, which attributes, what
+    // format for normals), as bytes; layout() tells where each attribute is
+    pub fn buffer(&self) -> Vec<u8>
+    {
+        self.vertex_buffer().0
+    }
+
+    // JSON LayoutDescriptor of buffer(): vertex count, stride, and offset, stride and format of each attribute
+    pub fn layout(&self) -> String
+    {
+        serde_json::to_string(&layout::describe(self.vertices.len() / 9, &self.layout)).unwrap_or_default()
+    }
+
+    // three indices in vertices() per triangle, for a load with the `indexed` option; empty otherwise
+    pub fn indices(&self) -> Vec<u32>
+    {
+        self.indices.clone()
+    }
+
+    // pick ID of each vertex of vertices(), to upload as a vertex attribute of an ID render pass: 0 is nothing,
+    // any other ID is 1 + an index in picks(). With the `indexed` option, the vertices a residue shares with the one
+    // before carry that one's ID: read IDs per triangle with triangle_pick_ids()
+    pub fn pick_ids(&self) -> Vec<u32>
+    {
+        self.pick_ids.clone()
+    }
+
+    // pick ID of each triangle, e.g. for the face index of a raycast hit: the highest ID of its vertices, since a
+    // triangle only shares vertices with residues drawn before its own
+    pub fn triangle_pick_ids(&self) -> Vec<u32>
+    {
+        let max = |corners: &[u32]| corners.iter().map(|&v| self.pick_ids[v as usize]).max().unwrap_or(0);
+        if self.indices.is_empty() {
+            self.pick_ids.chunks_exact(3).map(|ids| ids.iter().copied().max().unwrap_or(0)).collect()
         }
         else {
-            result.push(val);
+            self.indices.chunks_exact(3).map(max).collect()
         }
     }
 
-//    console_log!("{:?}", result);
-    return result;
+    // JSON array of Pick: the ORF, residue, codon and residues behind each pick ID
+    pub fn picks(&self) -> String
+    {
+        serde_json::to_string(&self.picks).unwrap_or_default()
+    }
+
+    // JSON array of StripInfo, in the order of the strips in vertices()
+    pub fn strips(&self) -> String
+    {
+        serde_json::to_string(&self.strips).unwrap_or_default()
+    }
+
+    // JSON array of LostOrf: the reference proteins no strip was matched with
+    pub fn lost(&self) -> String
+    {
+        serde_json::to_string(&self.lost).unwrap_or_default()
+    }
+
+    // JSON array of Variant: the nucleotide differences to the reference, with their effect on each protein
+    pub fn variants(&self) -> String
+    {
+        serde_json::to_string(&self.variants).unwrap_or_default()
+    }
 }
 
 
 #[wasm_bindgen]
 pub fn load_sequence(seq: &str, ref_seq: &str) -> Vec<f32>
 {
+    // both inputs may be raw sequences, (multi-)FASTA or GenBank; headers are never translated
+    let ref_records = input::parse(ref_seq).map(|input| input.records).unwrap_or_default();
+    let records = input::parse(seq).map(|input| input.records).unwrap_or_default();
+
     // convert data and references sequences to amino acids and separate into several lists based on START and STOP codons
-    let ref_enc = dna_to_aa(ref_seq);
-    let enc = dna_to_aa(seq);
-    
-    // compare the two lists of amino acids to determine what should be red
-    let mut data: Vec<Vec<i8>> = Vec::new();
-    for i in 0..enc.len() {
-        if i < ref_enc.len() {
-            let enc_i = enc[i].clone(); 
-            let ref_i = ref_enc[i].clone();
-            let res = compare_aa(enc_i, ref_i);
-            data.push(res);
+    let options = Options::default();
+    let ref_enc = records_to_aa(&ref_records, &genetic_code::STANDARD, &options);
+    let enc = records_to_aa(&records, &genetic_code::STANDARD, &options);
+    console_log!("parts count: {}", enc.len());
+
+    // Separate the vertex lists into positions, normals and colors
+    let pairings = variant::align_records(&records, &ref_records);
+    let variants = variant::call_records(&records, &ref_records, &pairings, &ref_enc, &genetic_code::STANDARD);
+    let (strips, lost) = proteins_to_strips(&enc, &ref_enc, &variants, &options);
+    Visualization::new(strips, &lost, variants).vertices
+}
+
+fn check_not_empty(records: &[fasta::Record], input: &str) -> Result<()>
+{
+    if records.iter().all(|r| r.sequence.is_empty()) {
+        return Err(Error::EmptySequence { input: input.to_string() });
+    }
+    Ok(())
+}
+
+// same as load_sequence, but throws a JS Error (with `kind`, `position`, ...) explaining why nothing could be drawn
+#[wasm_bindgen]
+pub fn try_load_sequence(seq: &str, ref_seq: &str) -> std::result::Result<Vec<f32>, JsValue>
+{
+    load_sequence_checked(seq, ref_seq, &Options::default()).map_err(JsValue::from)
+}
+
+// options: JSON object, see Options for the fields, e.g. '{"genetic_code": 2}'
+#[wasm_bindgen]
+pub fn load_sequence_with_options(seq: &str, ref_seq: &str, options: &str) -> std::result::Result<Vec<f32>, JsValue>
+{
+    let options = Options::from_json(options)?;
+    load_sequence_checked(seq, ref_seq, &options).map_err(JsValue::from)
+}
+
+pub fn load_sequence_checked(seq: &str, ref_seq: &str, options: &Options) -> Result<Vec<f32>>
+{
+    Ok(build_visualization(seq, ref_seq, options)?.vertices)
+}
+
+// same as load_sequence_with_options for byte inputs, see visualize_bytes: large compressed files cross the wasm
+// boundary as they are and are decompressed as they are parsed, without a JS string of the whole text
+#[wasm_bindgen]
+pub fn load_sequence_bytes(seq: &[u8], ref_seq: &[u8], options: &str) -> std::result::Result<Vec<f32>, JsValue>
+{
+    let options = Options::from_json(options)?;
+    Ok(build_visualization_from_bytes(seq, ref_seq, &options)?.vertices)
+}
+
+// like load_sequence_with_options, but also returns what each strip is, see Visualization
+#[wasm_bindgen]
+pub fn visualize(seq: &str, ref_seq: &str, options: &str) -> std::result::Result<Visualization, JsValue>
+{
+    let options = Options::from_json(options)?;
+    build_visualization(seq, ref_seq, &options).map_err(JsValue::from)
+}
+
+pub fn build_visualization(seq: &str, ref_seq: &str, options: &Options) -> Result<Visualization>
+{
+    visualize_records(input::parse(seq)?, input::parse(ref_seq)?, None, options)
+}
+
+// like visualize, always with the `indexed` option: each vertex is stored once, and positions(), normals(),
+// colors() and indices() are ready for an indexed draw (about a third of the memory of load_sequence)
+#[wasm_bindgen]
+pub fn load_sequence_indexed(seq: &str, ref_seq: &str, options: &str) -> std::result::Result<Visualization, JsValue>
+{
+    let options = Options { indexed: true, ..Options::from_json(options)? };
+    build_visualization(seq, ref_seq, &options).map_err(JsValue::from)
+}
+
+// like visualize, but the inputs are bytes (a Uint8Array from JS), plain or gzip / BGZF-compressed, e.g. a
+// .fasta.gz genome or a .fastq.gz file of reads; the compression is told by the first bytes
+#[wasm_bindgen]
+pub fn visualize_bytes(seq: &[u8], ref_seq: &[u8], options: &str) -> std::result::Result<Visualization, JsValue>
+{
+    let options = Options::from_json(options)?;
+    build_visualization_from_bytes(seq, ref_seq, &options).map_err(JsValue::from)
+}
+
+pub fn build_visualization_from_bytes(seq: &[u8], ref_seq: &[u8], options: &Options) -> Result<Visualization>
+{
+    // of a .2bit reference, only the region is read
+    visualize_records(input::parse_bytes(seq)?, input::parse_bytes_in(ref_seq, options.region.as_deref())?, None, options)
+}
+
+// the features given, or else those the reference brings, e.g. from a GenBank file
+fn features_or_own<'a>(features: Option<&'a [Feature]>, own: &'a [Feature]) -> Option<&'a [Feature]>
+{
+    match features {
+        Some(features) => Some(features),
+        None if !own.is_empty() => Some(own),
+        None => None,
+    }
+}
+
+// the proteins of the sample and of the reference: the CDS features of the reference, carried over to the sample,
+// when there are `features`, else those of the ORF finder
+fn proteins_of(records: &[fasta::Record], ref_records: &[fasta::Record], pairings: &[variant::Pairing], features: Option<&[Feature]>, code: &GeneticCode, options: &Options) -> Result<(Vec<Orf>, Vec<Orf>)>
+{
+    match features {
+        Some(features) => {
+            for record in records.iter().chain(ref_records) {
+                check_alphabet(&record.sequence).map_err(|e| e.in_record(&record.id))?;
+            }
+            annotation::annotated_orfs(records, ref_records, pairings, features, code)
         }
-        else {
-            let enc_i = enc[i].clone(); 
-            let ref_i: Vec<i8> = vec![-1; enc_i.len()];
-            let res = compare_aa(enc_i, ref_i);
-            data.push(res);
+        None => Ok((try_records_to_aa(records, code, options)?, try_records_to_aa(ref_records, code, options)?)),
+    }
+}
+
+// like visualize, but the proteins drawn are the CDS features of `annotation`, a GFF3 file or an NCBI feature table
+// of the reference, carried over to the sample through their alignment; gene names end up in the strip metadata
+#[wasm_bindgen]
+pub fn visualize_annotated(seq: &str, ref_seq: &str, annotation: &str, options: &str) -> std::result::Result<Visualization, JsValue>
+{
+    let options = Options::from_json(options)?;
+    build_annotated_visualization(seq, ref_seq, annotation, &options).map_err(JsValue::from)
+}
+
+pub fn build_annotated_visualization(seq: &str, ref_seq: &str, annotation: &str, options: &Options) -> Result<Visualization>
+{
+    let features = annotation::parse(annotation)?;
+    visualize_records(input::parse(seq)?, input::parse(ref_seq)?, Some(&features), options)
+}
+
+// proteins from the ORF finder, or from the CDS features of the reference when there are `features`; a GenBank
+// reference brings its own. Low-quality bases of the sample are masked first, and only the proteins of
+// `options.region` are kept
+fn visualize_records(mut sample: Input, reference: Input, features: Option<&[Feature]>, options: &Options) -> Result<Visualization>
+{
+    let code = options.genetic_code()?;
+
+    sample.mask(options.min_base_quality);
+    let records = sample.records;
+    let ref_records = reference.records;
+    let slices = reference.slices;
+    let slice_of = |r: usize| slices.get(r).and_then(|slice| slice.as_ref());
+    check_not_empty(&records, "sample")?;
+    check_not_empty(&ref_records, "reference")?;
+
+    let features = features_or_own(features, &reference.features);
+    let pairings = variant::align_records(&records, &ref_records);
+    let (mut enc, mut ref_enc) = proteins_of(&records, &ref_records, &pairings, features, code, options)?;
+    let mut variants = variant::call_records(&records, &ref_records, &pairings, &ref_enc, code);
+    // a reference record holding only a region of its sequence gives coordinates in the whole sequence
+    for (r, record) in ref_records.iter().enumerate() {
+        if let Some(slice) = slice_of(r) {
+            ref_enc.iter_mut().filter(|orf| orf.record == record.id).for_each(|orf| orf.move_to(slice));
+            variants.iter_mut().filter(|v| v.record == record.id).for_each(|v| v.move_to(slice));
         }
     }
 
-    // convert each amino acid list into a strip of vertices
-    let mut vertices: Vec<Vertex> = Vec::new();
-    for i in 0..data.len() {
-        let vtx: Vec<i8> = data[i].clone();
-        vertices.append(& mut aa_to_strip(vtx, data.len() as i32, i as i32));
+    if let Some(text) = &options.region {
+        // in reference coordinates, and lifted over to the sample for the sample proteins
+        let lengths: Vec<(String, usize)> = ref_records.iter().enumerate()
+            .map(|(r, record)| (record.id.clone(), slice_of(r).map_or(record.sequence.len(), |slice| slice.length)))
+            .collect();
+        let region = region::resolve_among(text, features.unwrap_or(&[]), &lengths)?;
+        let in_record = match slice_of(region.index) {
+            Some(slice) => region.within(slice),
+            None => region.clone(),
+        };
+        let sample_regions = in_record.in_sample(&records, &ref_records, &pairings);
+        enc.retain(|orf| sample_regions.iter().any(|r| r.overlaps(&records, &orf.record, &(orf.start..orf.end))));
+        ref_enc.retain(|orf| region.overlaps(&ref_records, &orf.record, &(orf.start..orf.end)));
+        variants.retain(|v| region.overlaps(&ref_records, &v.record, &v.reference_range()));
+        if enc.is_empty() && ref_enc.is_empty() {
+            return Err(Error::InvalidRegion { region: text.clone(), message: "no protein overlaps it".to_string() });
+        }
+    }
+    else if enc.is_empty() {
+        return Err(Error::NoOpenReadingFrame { input: "sample".to_string() });
+    }
+    else if ref_enc.is_empty() {
+        return Err(Error::NoOpenReadingFrame { input: "reference".to_string() });
     }
 
-    // Separate the vertex lists into positions, normals and colors
-    let arr = vertices_to_float(vertices);
-    return arr;
-}
\ No newline at end of file
+    let (strips, lost) = proteins_to_strips(&enc, &ref_enc, &variants, options);
+    let mut visualization = Visualization::new(strips, &lost, variants);
+    visualization.layout = options.layout.clone();
+    Ok(visualization)
+}
+
+// VCF 4.3 text of the differences between the sample and the reference, with their effect on the reference ORFs
+#[wasm_bindgen]
+pub fn export_vcf(seq: &str, ref_seq: &str, options: &str) -> std::result::Result<String, JsValue>
+{
+    let options = Options::from_json(options)?;
+    build_vcf(seq, ref_seq, &options).map_err(JsValue::from)
+}
+
+pub fn build_vcf(seq: &str, ref_seq: &str, options: &Options) -> Result<String>
+{
+    vcf_of_records(input::parse(seq)?, input::parse(ref_seq)?, None, options)
+}
+
+// like export_vcf, but the effects are on the CDS features of `annotation`, a GFF3 file or an NCBI feature table of
+// the reference, and the ORF field names their genes
+#[wasm_bindgen]
+pub fn export_annotated_vcf(seq: &str, ref_seq: &str, annotation: &str, options: &str) -> std::result::Result<String, JsValue>
+{
+    let options = Options::from_json(options)?;
+    build_annotated_vcf(seq, ref_seq, annotation, &options).map_err(JsValue::from)
+}
+
+pub fn build_annotated_vcf(seq: &str, ref_seq: &str, annotation: &str, options: &Options) -> Result<String>
+{
+    let features = annotation::parse(annotation)?;
+    vcf_of_records(input::parse(seq)?, input::parse(ref_seq)?, Some(&features), options)
+}
+
+// the effects are on the same proteins as in visualize_records: the CDS features when there are any (a GenBank
+// reference brings its own), else the ORF finder's
+fn vcf_of_records(mut sample: Input, reference: Input, features: Option<&[Feature]>, options: &Options) -> Result<String>
+{
+    let code = options.genetic_code()?;
+
+    sample.mask(options.min_base_quality);
+    let records = sample.records;
+    let ref_records = reference.records;
+    check_not_empty(&records, "sample")?;
+    check_not_empty(&ref_records, "reference")?;
+
+    let features = features_or_own(features, &reference.features);
+    let pairings = variant::align_records(&records, &ref_records);
+    let (_, ref_enc) = proteins_of(&records, &ref_records, &pairings, features, code, options)?;
+    let variants = variant::call_records(&records, &ref_records, &pairings, &ref_enc, code);
+    Ok(vcf::write(&variants, &ref_records))
+}
+
+// the reference with the variants of a VCF file applied, as FASTA text ready for load_sequence
+#[wasm_bindgen]
+pub fn apply_vcf(ref_seq: &str, vcf_text: &str) -> std::result::Result<String, JsValue>
+{
+    build_consensus(ref_seq, vcf_text).map_err(JsValue::from)
+}
+
+pub fn build_consensus(ref_seq: &str, vcf_text: &str) -> Result<String>
+{
+    let ref_records = input::parse(ref_seq)?.records;
+    check_not_empty(&ref_records, "reference")?;
+    let variants = vcf::parse(vcf_text)?;
+    Ok(fasta::write(&vcf::apply(&ref_records, &variants)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFERENCE: &str = include_str!("../../public/data/covid.txt");
+    const SAMPLE: &str = include_str!("../../public/data/covid_variant.txt");
+
+    #[test]
+    fn crowded_rings_have_finite_vertices()
+    {
+        // hundreds of strips to a ring
+        for options in [r#"{"frames": "all"}"#, r#"{"genetic_code": 11, "alternative_starts": true}"#] {
+            let visualization = build_visualization(SAMPLE, REFERENCE, &Options::from_json(options).unwrap()).unwrap();
+            assert!(visualization.strips.len() > 200);
+            assert!(visualization.vertices.iter().all(|x| x.is_finite()), "{}", options);
+        }
+    }
+
+    #[test]
+    fn picks_point_to_their_residue()
+    {
+        // ATG, then Phe Leu Ser Tyr Cys and a stop
+        let sequence = "ATGTTTCTGTCTTATTGTTAA";
+        for json in [r#"{}"#, r#"{"indexed": true}"#] {
+            let visualization = build_visualization(sequence, sequence, &Options::from_json(json).unwrap()).unwrap();
+            let (strip, pick_ids) = (&visualization.strips[0], visualization.pick_ids());
+            let pick = |id: u32| &visualization.picks[id as usize - 1];
+            let triangles = visualization.triangle_pick_ids();
+            assert_eq!(visualization.picks.len(), 6);
+            for (i, p) in visualization.picks.iter().enumerate() {
+                assert_eq!((p.strip, p.residue, p.start, p.end), (0, i, 3 * i, 3 * i + 3), "{}", json);
+                assert_eq!(p.codon, sequence[3 * i..3 * i + 3], "{}", json);
+            }
+            // Leu: its vertices, but the row it shares with Phe in an indexed mesh
+            let leu: Vec<usize> = (0..pick_ids.len()).filter(|&v| pick_ids[v] != 0 && pick(pick_ids[v]).residue == 2).collect();
+            assert!(!leu.is_empty() && leu.iter().all(|&v| pick(pick_ids[v]).sample == AminoAcid::Leu), "{}", json);
+            // every triangle has the ID of the residue it is drawn for, and every residue is drawn
+            let per_residue: Vec<usize> = (1..=6).map(|id| triangles.iter().filter(|&&t| t == id).count()).collect();
+            assert!(per_residue.iter().all(|&count| count > 0), "{} {:?}", json, per_residue);
+            assert_eq!(per_residue.iter().sum::<usize>(), triangles.len());
+            if json.contains("indexed") {
+                assert_eq!(triangles.len(), strip.index_count / 3);
+                // a triangle of Leu on the row it shares with Phe
+                let on_shared_row = visualization.indices.chunks_exact(3).zip(&triangles)
+                    .any(|(corners, &id)| id == 3 && corners.iter().any(|&v| pick_ids[v as usize] == 2));
+                assert!(on_shared_row);
+            }
+        }
+    }
+
+    #[test]
+    fn twobit_region_keeps_genome_coordinates()
+    {
+        let genome: String = REFERENCE.split_whitespace().collect();
+        let file = twobit::tests::encode(&[("MN908947.3", &genome)]);
+        let options = Options::from_json(r#"{"region": "MN908947.3:21563-25384"}"#).unwrap();
+        let visualization = build_visualization_from_bytes(SAMPLE.as_bytes(), &file, &options).unwrap();
+        // S, read on its own, and R765C in it
+        let variant = visualization.called_variants().iter().find(|v| v.position == 23854).unwrap();
+        let effect = &variant.effects[0];
+        assert_eq!((effect.frame.to_string(), effect.start, effect.end, effect.residue), ("+2".to_string(), 21562, 25384, 765));
+    }
+
+    #[test]
+    fn region_is_lifted_to_every_read()
+    {
+        let genome: String = SAMPLE.split_whitespace().collect();
+        // two reads holding all of S, one far from it
+        let reads = format!(">one\n{}\n>two\n{}\n>far\n{}\n", &genome[21000..25500], &genome[21400..26000], &genome[1000..5500]);
+        let options = Options::from_json(r#"{"region": "23000-24000"}"#).unwrap();
+        let visualization = build_visualization(&reads, REFERENCE, &options).unwrap();
+        let records: Vec<&str> = visualization.strips.iter().map(|strip| strip.record.as_str()).collect();
+        assert!(records.contains(&"one") && records.contains(&"two"));
+        assert!(!records.contains(&"far"));
+    }
+
+    // ORF1ab, with its -1 frameshift, and S
+    const ANNOTATION: &str = "##gff-version 3
+MN908947.3\t.\tCDS\t266\t13468\t.\t+\t0\tID=cds-1;gene=ORF1ab
+MN908947.3\t.\tCDS\t13468\t21555\t.\t+\t0\tID=cds-1;gene=ORF1ab
+MN908947.3\t.\tCDS\t21563\t25384\t.\t+\t0\tID=cds-2;gene=S
+";
+
+    #[test]
+    fn annotated_vcf_names_genes()
+    {
+        let reference = format!(">MN908947.3\n{}", REFERENCE);
+        let vcf = build_annotated_vcf(SAMPLE, &reference, ANNOTATION, &Options::default()).unwrap();
+        let info: Vec<&str> = vcf.lines().filter(|line| !line.starts_with('#')).map(|line| line.rsplit('\t').next().unwrap()).collect();
+        assert_eq!(info, vec![
+            "ORF=ORF1ab;AA=L6501V;CSQ=missense",
+            "ORF=ORF1ab;AA=N6525I;CSQ=missense",
+            "ORF=S;AA=R765C;CSQ=missense",
+        ]);
+
+        // a seqid the reference does not have
+        let other = ANNOTATION.replace("MN908947.3", "NC_045512.2");
+        match build_annotated_vcf(SAMPLE, &reference, &other, &Options::default()) {
+            Err(Error::UnknownRecord { record }) => assert_eq!(record, "NC_045512.2"),
+            result => panic!("{:?}", result),
+        }
+        assert!(build_annotated_visualization(SAMPLE, &reference, &other, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn phase_moves_the_first_codon()
+    {
+        let reference = format!(">MN908947.3\n{}", REFERENCE);
+        // S annotated from the base before its start codon, with phase 1
+        let shifted = ANNOTATION.replace("21563\t25384\t.\t+\t0", "21562\t25384\t.\t+\t1");
+        let vcf = build_annotated_vcf(SAMPLE, &reference, &shifted, &Options::default()).unwrap();
+        assert!(vcf.contains("ORF=S;AA=R765C;CSQ=missense"));
+    }
+}
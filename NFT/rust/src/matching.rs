@@ -0,0 +1,213 @@
+//! Pairs the proteins of the sample with those of the reference by sequence similarity: k-mer Jaccard
+//! similarity picks the candidates, the alignment score decides between them. Each protein is in at most one pair.
+
+use std::collections::HashMap;
+
+use crate::align::{self, Alignment, AlignmentMode, Scoring};
+use crate::amino_acid::AminoAcid;
+use crate::orf::Orf;
+
+// residues per k-mer
+const K: usize = 3;
+// pairs sharing fewer k-mers are never matched
+const MIN_SIMILARITY: f32 = 0.2;
+// reference proteins aligned against each sample protein, the most similar first
+const MAX_CANDIDATES: usize = 4;
+// pairs whose alignment scores less than this fraction of the reference protein aligned with itself are never
+// matched: the sample protein is novel and the reference protein lost, unless another pair takes them
+const MIN_SCORE: f32 = 0.1;
+
+/// A sample protein and a reference protein, or one of them alone: a novel protein of the sample
+/// (`reference` is None) or a protein lost from the reference (`sample` is None).
+#[derive(Debug, Clone)]
+pub struct OrfMatch {
+    /// Index into the sample proteins.
+    pub sample: Option<usize>,
+    /// Index into the reference proteins.
+    pub reference: Option<usize>,
+    /// Jaccard similarity of the k-mer sets of the two proteins, 0 when unmatched.
+    pub similarity: f32,
+    /// Alignment of the sample protein to the reference protein, with no columns when unmatched.
+    pub alignment: Alignment,
+}
+
+// distinct k-mers of a protein, sorted
+fn kmers(protein: &[AminoAcid]) -> Vec<u32>
+{
+    let mut kmers: Vec<u32> = protein.windows(K)
+        .map(|w| w.iter().fold(0, |code, &aa| code * 22 + aa as u32))
+        .collect();
+    kmers.sort_unstable();
+    kmers.dedup();
+    kmers
+}
+
+fn jaccard(shared: usize, a: usize, b: usize) -> f32
+{
+    shared as f32 / (a + b - shared) as f32
+}
+
+// k-mers two sorted k-mer sets have in common
+fn shared(a: &[u32], b: &[u32]) -> usize
+{
+    let (mut i, mut j, mut count) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                count += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Matches `sample` proteins to `reference` proteins. Annotated proteins are matched by gene name, however
+/// different they are; the others by similarity, if they also align well enough. The result holds every matched pair in the order of
+/// the sample, then the novel sample proteins, then the lost reference proteins.
+pub fn match_orfs(sample: &[&Orf], reference: &[&Orf], mode: AlignmentMode, scoring: &Scoring) -> Vec<OrfMatch>
+{
+    let sample_kmers: Vec<Vec<u32>> = sample.iter().map(|orf| kmers(&orf.protein)).collect();
+    let reference_kmers: Vec<Vec<u32>> = reference.iter().map(|orf| kmers(&orf.protein)).collect();
+
+    // which reference proteins contain each k-mer
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (r, kmers) in reference_kmers.iter().enumerate() {
+        for &kmer in kmers {
+            index.entry(kmer).or_default().push(r);
+        }
+    }
+
+    // (sample, reference, similarity, alignment, score relative to the reference aligned with itself)
+    let mut candidates: Vec<(usize, usize, f32, Alignment, f32)> = Vec::new();
+    for (s, orf) in sample.iter().enumerate() {
+        let mut similar: Vec<(usize, f32)> = Vec::new();
+        let named = orf.name.as_ref().and_then(|name| reference.iter().position(|r| r.name.as_ref() == Some(name)));
+        if let Some(r) = named {
+            let (a, b) = (&sample_kmers[s], &reference_kmers[r]);
+            let similarity = if a.is_empty() && b.is_empty() { 1.0 } else { jaccard(shared(a, b), a.len(), b.len()) };
+            similar.push((r, similarity));
+        }
+        else if sample_kmers[s].is_empty() {
+            // too short for k-mers: only an identical protein will do
+            similar.extend(reference.iter().enumerate()
+                .filter(|(_, reference)| reference.protein == orf.protein)
+                .map(|(r, _)| (r, 1.0)));
+        }
+        else {
+            let mut shared: HashMap<usize, usize> = HashMap::new();
+            for kmer in &sample_kmers[s] {
+                for &r in index.get(kmer).into_iter().flatten() {
+                    *shared.entry(r).or_default() += 1;
+                }
+            }
+            similar.extend(shared.into_iter()
+                .map(|(r, shared)| (r, jaccard(shared, sample_kmers[s].len(), reference_kmers[r].len())))
+                .filter(|&(_, similarity)| similarity >= MIN_SIMILARITY));
+        }
+        similar.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+
+        for &(r, similarity) in similar.iter().take(MAX_CANDIDATES) {
+            let protein = &reference[r].protein;
+            let alignment = align::align(&orf.protein, protein, mode, scoring);
+            let best = align::positional(protein, protein, scoring).score.max(1);
+            let relative = alignment.score as f32 / best as f32;
+            if relative >= MIN_SCORE || named == Some(r) {
+                candidates.push((s, r, similarity, alignment, relative));
+            }
+        }
+    }
+
+    // same gene first, then best pairs; ties go to the most similar, then to the first proteins
+    let same_name = |s: usize, r: usize| sample[s].name.is_some() && sample[s].name == reference[r].name;
+    candidates.sort_by(|a, b| {
+        same_name(b.0, b.1).cmp(&same_name(a.0, a.1))
+            .then(b.4.partial_cmp(&a.4).unwrap())
+            .then(b.2.partial_cmp(&a.2).unwrap())
+            .then(a.0.cmp(&b.0))
+            .then(a.1.cmp(&b.1))
+    });
+
+    let mut sample_match: Vec<Option<(usize, f32, Alignment)>> = vec![None; sample.len()];
+    let mut reference_matched = vec![false; reference.len()];
+    for (s, r, similarity, alignment, _) in candidates {
+        if sample_match[s].is_none() && !reference_matched[r] {
+            sample_match[s] = Some((r, similarity, alignment));
+            reference_matched[r] = true;
+        }
+    }
+
+    let mut matches: Vec<OrfMatch> = Vec::new();
+    let mut novel: Vec<OrfMatch> = Vec::new();
+    for (s, found) in sample_match.into_iter().enumerate() {
+        match found {
+            Some((r, similarity, alignment)) => {
+                matches.push(OrfMatch { sample: Some(s), reference: Some(r), similarity, alignment });
+            }
+            None => {
+                novel.push(OrfMatch { sample: Some(s), reference: None, similarity: 0.0, alignment: Alignment::empty() });
+            }
+        }
+    }
+    matches.append(&mut novel);
+    for (r, matched) in reference_matched.into_iter().enumerate() {
+        if !matched {
+            matches.push(OrfMatch { sample: None, reference: Some(r), similarity: 0.0, alignment: Alignment::empty() });
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Frame;
+    use crate::scoring::ScoringMatrix;
+    use crate::testing::protein;
+
+    const SCORING: Scoring = Scoring { matrix: ScoringMatrix::Blosum62, gap_open: 11, gap_extend: 1 };
+
+    fn orf(protein: Vec<AminoAcid>, name: Option<&str>) -> Orf
+    {
+        let end = 3 * (protein.len() + 1);
+        Orf { record: "chr".to_string(), frame: Frame::FORWARD_1, start: 0, end, protein, codons: Vec::new(), segments: Vec::new(), name: name.map(str::to_string) }
+    }
+
+    // (sample, reference) of each match, in order
+    fn pairs(sample: &[Orf], reference: &[Orf]) -> Vec<(Option<usize>, Option<usize>)>
+    {
+        let (sample, reference): (Vec<&Orf>, Vec<&Orf>) = (sample.iter().collect(), reference.iter().collect());
+        match_orfs(&sample, &reference, AlignmentMode::Global, &SCORING).iter().map(|m| (m.sample, m.reference)).collect()
+    }
+
+    #[test]
+    fn similar_proteins_pair_up()
+    {
+        let (a, b, c) = (protein(200, 1), protein(150, 2), protein(180, 3));
+        let mut mutated = a.clone();
+        mutated[10] = AminoAcid::Trp;
+        mutated.drain(50..53);
+        let sample = [orf(b.clone(), None), orf(mutated, None)];
+        let reference = [orf(c, None), orf(a, None)];
+        // the mutated protein goes with its reference, b is novel and c lost
+        assert_eq!(pairs(&sample, &reference), vec![(Some(1), Some(1)), (Some(0), None), (None, Some(0))]);
+    }
+
+    #[test]
+    fn shared_kmers_without_a_good_alignment_are_no_match()
+    {
+        // the same 3-mers as the reference, in blocks of 4 residues put in reverse order
+        let reference = protein(120, 4);
+        let shuffled: Vec<AminoAcid> = reference.chunks(4).rev().flatten().copied().collect();
+        let (a, b) = (kmers(&shuffled), kmers(&reference));
+        assert!(jaccard(shared(&a, &b), a.len(), b.len()) >= MIN_SIMILARITY);
+        assert_eq!(pairs(&[orf(shuffled.clone(), None)], &[orf(reference.clone(), None)]), vec![(Some(0), None), (None, Some(0))]);
+
+        // unless they are the same gene
+        let named = pairs(&[orf(shuffled, Some("g"))], &[orf(reference, Some("g"))]);
+        assert_eq!(named, vec![(Some(0), Some(0))]);
+    }
+}
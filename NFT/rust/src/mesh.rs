@@ -0,0 +1,143 @@
+//! Indexed triangle mesh: each vertex once, and three u32 indices per triangle. A strip of quads shares the two
+//! vertices of every edge between two quads, and the edge between the strips of two consecutive residues.
+
+use crate::Vertex;
+
+// corners of the two triangles of a quad, from its first row (top, bottom) to the next, as in points_to_quad
+const QUAD: [u32; 6] = [0, 1, 3, 0, 3, 2];
+
+// the end of a strip and the start of the next are computed apart, and may differ by a rounding error
+const TOLERANCE: f32 = 1e-5;
+
+fn same(a: &Vertex, b: &Vertex) -> bool
+{
+    glm::distance(&a.position, &b.position) < TOLERANCE
+        && glm::distance(&a.normal, &b.normal) < TOLERANCE
+        && glm::distance(&a.color, &b.color) < TOLERANCE
+}
+
+#[derive(Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // index of the top vertex of the last row of each lane, while a strip can go on from it
+    last_rows: Vec<Option<u32>>,
+}
+
+impl Mesh {
+    pub fn new() -> Mesh
+    {
+        Mesh::default()
+    }
+
+    /// Appends a strip given as rows of two points, as strip_to_vertices takes them. The strip goes on from the
+    /// previous one of the same lane if its first row is the last row of that one, with the same normals and
+    /// colors, up to rounding. Lanes are the faces of an extruded strip; a ribbon has a single lane, 0.
+    pub fn append_strip(&mut self, lane: usize, points: &[glm::Vec3], normals: &[glm::Vec3], colors: &[glm::Vec3])
+    {
+        if self.last_rows.len() <= lane {
+            self.last_rows.resize(lane + 1, None);
+        }
+        let rows = points.len() / 2;
+        if rows < 2 {
+            return;
+        }
+        let vertex = |i: usize| Vertex { position: points[i], normal: normals[i], color: colors[i] };
+
+        let shared = match self.last_rows[lane] {
+            Some(last) => same(&self.vertices[last as usize], &vertex(0)) && same(&self.vertices[last as usize + 1], &vertex(1)),
+            None => false,
+        };
+        let mut row = match self.last_rows[lane] {
+            Some(last) if shared => last,
+            _ => {
+                self.vertices.push(vertex(0));
+                self.vertices.push(vertex(1));
+                self.vertices.len() as u32 - 2
+            }
+        };
+        for i in 1..rows {
+            self.vertices.push(vertex(2 * i));
+            self.vertices.push(vertex(2 * i + 1));
+            let next = self.vertices.len() as u32 - 2;
+            // the quad corners 0, 1 are the first row, 2, 3 the next one
+            let corners = [row, row + 1, next, next + 1];
+            self.indices.extend(QUAD.iter().map(|&corner| corners[corner as usize]));
+            row = next;
+        }
+        self.last_rows[lane] = Some(row);
+    }
+
+    /// Appends a fan of triangles around `center`, closing the loop of `rim`.
+    pub fn append_fan(&mut self, center: Vertex, rim: &[Vertex])
+    {
+        let first = self.vertices.len() as u32;
+        self.vertices.push(center);
+        self.vertices.extend_from_slice(rim);
+        let count = rim.len() as u32;
+        for i in 0..count {
+            self.indices.extend_from_slice(&[first, first + 1 + i, first + 1 + (i + 1) % count]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a straight strip along x from `from` to `from + rows - 1`, one unit wide, at height `z`
+    fn strip(from: f32, rows: usize, z: f32) -> Vec<glm::Vec3>
+    {
+        (0..rows).flat_map(|i| [glm::vec3(from + i as f32, 0.0, z), glm::vec3(from + i as f32, 1.0, z)]).collect()
+    }
+
+    fn append(mesh: &mut Mesh, lane: usize, points: &[glm::Vec3], normal: glm::Vec3, color: glm::Vec3)
+    {
+        mesh.append_strip(lane, points, &vec![normal; points.len()], &vec![color; points.len()]);
+    }
+
+    fn indices_in_range(mesh: &Mesh) -> bool
+    {
+        mesh.indices.iter().all(|&i| (i as usize) < mesh.vertices.len())
+    }
+
+    #[test]
+    fn consecutive_strips_share_their_edge()
+    {
+        let (up, red) = (glm::vec3(0.0, 0.0, 1.0), glm::vec3(1.0, 0.0, 0.0));
+        let mut mesh = Mesh::new();
+        append(&mut mesh, 0, &strip(0.0, 3, 0.0), up, red);
+        // starts on the last row of the first one, up to rounding
+        append(&mut mesh, 0, &strip(2.0 + 1e-7, 3, 0.0), up, red);
+        assert_eq!(mesh.vertices.len(), 6 + 4);
+        assert_eq!(mesh.indices.len(), 4 * 6);
+        assert!(indices_in_range(&mesh));
+
+        // another color, then another normal: both are new vertices
+        append(&mut mesh, 0, &strip(4.0, 2, 0.0), up, glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(mesh.vertices.len(), 10 + 4);
+        append(&mut mesh, 0, &strip(5.0, 2, 0.0), glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(mesh.vertices.len(), 14 + 4);
+        // a gap
+        append(&mut mesh, 0, &strip(7.0, 2, 0.0), glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(mesh.vertices.len(), 18 + 4);
+        assert!(indices_in_range(&mesh));
+    }
+
+    #[test]
+    fn faces_go_on_in_their_own_lane()
+    {
+        let (up, down, red) = (glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, 0.0, -1.0), glm::vec3(1.0, 0.0, 0.0));
+        let mut mesh = Mesh::new();
+        // top and bottom face of two residues of an extruded strip, face by face
+        append(&mut mesh, 0, &strip(0.0, 2, 1.0), up, red);
+        append(&mut mesh, 1, &strip(0.0, 2, 0.0), down, red);
+        append(&mut mesh, 0, &strip(1.0, 2, 1.0), up, red);
+        append(&mut mesh, 1, &strip(1.0, 2, 0.0), down, red);
+        assert_eq!(mesh.vertices.len(), 4 + 4 + 2 + 2);
+        assert_eq!(mesh.indices.len(), 4 * 6);
+        assert!(indices_in_range(&mesh));
+        // the second quad of the bottom face starts on the last row of the first one
+        assert_eq!(mesh.indices[18..20], [4 + 2, 4 + 3]);
+    }
+}
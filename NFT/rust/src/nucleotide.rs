@@ -0,0 +1,154 @@
+//! Nucleotide alphabet: A, C, G, T (U is read as T) plus the IUPAC ambiguity codes.
+//! Each code is stored as the set of bases it stands for, one bit per base.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[repr(u8)]
+pub enum Nucleotide {
+    A = 0b0001,
+    C = 0b0010,
+    G = 0b0100,
+    T = 0b1000,
+    /// A or G (purine)
+    R = 0b0101,
+    /// C or T (pyrimidine)
+    Y = 0b1010,
+    /// G or C (strong)
+    S = 0b0110,
+    /// A or T (weak)
+    W = 0b1001,
+    /// G or T (keto)
+    K = 0b1100,
+    /// A or C (amino)
+    M = 0b0011,
+    /// not A
+    B = 0b1110,
+    /// not C
+    D = 0b1101,
+    /// not G
+    H = 0b1011,
+    /// not T
+    V = 0b0111,
+    /// any base
+    N = 0b1111,
+}
+
+/// The four unambiguous bases, in bit order.
+pub const BASES: [Nucleotide; 4] = [Nucleotide::A, Nucleotide::C, Nucleotide::G, Nucleotide::T];
+
+impl Nucleotide {
+    /// Parses one base, case-insensitively. `U` is accepted as `T`.
+    pub fn from_char(c: char) -> Option<Nucleotide>
+    {
+        let nuc = match c.to_ascii_uppercase() {
+            'A' => Nucleotide::A,
+            'C' => Nucleotide::C,
+            'G' => Nucleotide::G,
+            'T' | 'U' => Nucleotide::T,
+            'R' => Nucleotide::R,
+            'Y' => Nucleotide::Y,
+            'S' => Nucleotide::S,
+            'W' => Nucleotide::W,
+            'K' => Nucleotide::K,
+            'M' => Nucleotide::M,
+            'B' => Nucleotide::B,
+            'D' => Nucleotide::D,
+            'H' => Nucleotide::H,
+            'V' => Nucleotide::V,
+            'N' => Nucleotide::N,
+            _ => return None,
+        };
+        Some(nuc)
+    }
+
+    fn from_mask(mask: u8) -> Nucleotide
+    {
+        match mask & 0b1111 {
+            0b0001 => Nucleotide::A,
+            0b0010 => Nucleotide::C,
+            0b0100 => Nucleotide::G,
+            0b1000 => Nucleotide::T,
+            0b0101 => Nucleotide::R,
+            0b1010 => Nucleotide::Y,
+            0b0110 => Nucleotide::S,
+            0b1001 => Nucleotide::W,
+            0b1100 => Nucleotide::K,
+            0b0011 => Nucleotide::M,
+            0b1110 => Nucleotide::B,
+            0b1101 => Nucleotide::D,
+            0b1011 => Nucleotide::H,
+            0b0111 => Nucleotide::V,
+            _ => Nucleotide::N,
+        }
+    }
+
+    /// Upper-case IUPAC letter.
+    pub fn to_char(self) -> char
+    {
+        match self {
+            Nucleotide::A => 'A',
+            Nucleotide::C => 'C',
+            Nucleotide::G => 'G',
+            Nucleotide::T => 'T',
+            Nucleotide::R => 'R',
+            Nucleotide::Y => 'Y',
+            Nucleotide::S => 'S',
+            Nucleotide::W => 'W',
+            Nucleotide::K => 'K',
+            Nucleotide::M => 'M',
+            Nucleotide::B => 'B',
+            Nucleotide::D => 'D',
+            Nucleotide::H => 'H',
+            Nucleotide::V => 'V',
+            Nucleotide::N => 'N',
+        }
+    }
+
+    pub fn is_ambiguous(self) -> bool
+    {
+        (self as u8).count_ones() > 1
+    }
+
+    /// Watson-Crick complement; ambiguity codes map to the code of the complemented set (R <-> Y, B <-> V, ...).
+    pub fn complement(self) -> Nucleotide
+    {
+        // A <-> T is bit 0 <-> bit 3, C <-> G is bit 1 <-> bit 2
+        let mask = self as u8;
+        let swapped = (mask & 0b0001) << 3 | (mask & 0b1000) >> 3 | (mask & 0b0010) << 1 | (mask & 0b0100) >> 1;
+        Nucleotide::from_mask(swapped)
+    }
+
+    /// The unambiguous bases this code stands for.
+    pub fn expand(self) -> impl Iterator<Item = Nucleotide>
+    {
+        BASES.iter().copied().filter(move |b| (self as u8) & (*b as u8) != 0)
+    }
+}
+
+/// Expands an ambiguous codon into every unambiguous codon it can stand for.
+pub fn expand_codon(codon: [Nucleotide; 3]) -> Vec<[Nucleotide; 3]>
+{
+    let mut codons: Vec<[Nucleotide; 3]> = Vec::new();
+    for m in codon[0].expand() {
+        for n in codon[1].expand() {
+            for o in codon[2].expand() {
+                codons.push([m, n, o]);
+            }
+        }
+    }
+    codons
+}
+
+/// Reverse complement of a sequence string. Letter case is kept; characters that are not
+/// nucleotides are kept as they are.
+pub fn reverse_complement(seq: &str) -> String
+{
+    seq.chars().rev().map(|c| {
+        match Nucleotide::from_char(c) {
+            Some(nuc) if c.is_ascii_lowercase() => nuc.complement().to_char().to_ascii_lowercase(),
+            Some(nuc) => nuc.complement().to_char(),
+            None => c,
+        }
+    }).collect()
+}
@@ -0,0 +1,142 @@
+//! Settings for a load. From JS they are passed as a JSON object; missing fields keep their default.
+
+use serde::Deserialize;
+
+use crate::align::{AlignmentMode, Scoring};
+use crate::extrude::CrossSections;
+use crate::frame::FrameSelection;
+use crate::genetic_code::GeneticCode;
+use crate::layout::VertexLayout;
+use crate::scoring::ScoringMatrix;
+use crate::spline::PathOptions;
+use crate::variant::Effect;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// NCBI translation table number, 1 is the standard code.
+    pub genetic_code: u8,
+    /// Also open reading frames at the table's alternative start codons (GTG, TTG, ...), not only at ATG.
+    pub alternative_starts: bool,
+    /// Reading frames to draw: "+1" .. "-3", "all" (one ring per frame) or "best".
+    pub frames: FrameSelection,
+    /// ORFs with fewer residues are dropped. The start codon counts, the stop codon does not.
+    pub min_orf_length: usize,
+    /// Also report the ORFs opened by start codons inside another ORF of the same frame.
+    pub nested_orfs: bool,
+    /// Keep ORFs overlapping a longer ORF of another frame, or one of the same length in an earlier frame.
+    pub overlapping_orfs: bool,
+    /// How sample proteins are compared to their reference: "global", "local" or "positional".
+    pub alignment: AlignmentMode,
+    /// "blosum62" or "pam250".
+    pub scoring_matrix: ScoringMatrix,
+    /// A gap of k residues costs gap_open + k * gap_extend.
+    pub gap_open: i32,
+    pub gap_extend: i32,
+    /// Colors of the residues hit by each class of nucleotide variant.
+    pub effect_colors: EffectColors,
+    /// FASTQ bases with a lower Phred score are read as N. 0 keeps every base.
+    pub min_base_quality: u8,
+    /// Color of the residues translated from a codon with an ambiguous or masked base; null draws them as any other.
+    pub uncertain_color: Option<[f32; 3]>,
+    /// Only draw the proteins overlapping this region of the reference: "chr:start-end" (1-based, inclusive; the
+    /// record name may be left out for a single record) or the name of an annotated gene.
+    pub region: Option<String>,
+    /// Store each vertex once and draw triangles through an index buffer, see Visualization::indices.
+    pub indexed: bool,
+    /// Layout of Visualization::buffer, e.g. {"arrangement": "interleaved", "normals": "snorm8x4"}.
+    pub layout: VertexLayout,
+    /// Shape swept along the residues of each side chain class: {"shape": "ribbon"}, "circle", "ellipse" or
+    /// "rectangle", e.g. {"nonpolar": {"shape": "circle", "scale": 0.5}}. Flat ribbons by default.
+    pub cross_sections: CrossSections,
+    /// Spline the strips follow through the residues, e.g. {"curve": "bezier", "tension": 0.5, "density": 2}.
+    pub path: PathOptions,
+}
+
+/// One [r, g, b] color per variant effect, components in 0..1. A null color leaves the residue as it is drawn
+/// from the protein comparison.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EffectColors {
+    pub synonymous: Option<[f32; 3]>,
+    pub missense: Option<[f32; 3]>,
+    pub stop_loss: Option<[f32; 3]>,
+    pub nonsense: Option<[f32; 3]>,
+    pub frameshift: Option<[f32; 3]>,
+}
+
+impl Default for EffectColors {
+    fn default() -> EffectColors
+    {
+        EffectColors {
+            synonymous: Some([0.2, 0.8, 0.2]),
+            missense: Some([1.0, 0.0, 0.0]),
+            stop_loss: Some([0.0, 0.8, 0.8]),
+            nonsense: Some([1.0, 1.0, 0.0]),
+            frameshift: Some([1.0, 0.0, 0.5]),
+        }
+    }
+}
+
+impl EffectColors {
+    pub fn of(&self, effect: Effect) -> Option<[f32; 3]>
+    {
+        match effect {
+            Effect::Synonymous => self.synonymous,
+            Effect::Missense => self.missense,
+            Effect::StopLoss => self.stop_loss,
+            Effect::Nonsense => self.nonsense,
+            Effect::Frameshift => self.frameshift,
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options
+    {
+        Options {
+            genetic_code: 1,
+            alternative_starts: false,
+            frames: FrameSelection::default(),
+            // a lone start codon followed by a stop is not worth drawing
+            min_orf_length: 2,
+            nested_orfs: false,
+            overlapping_orfs: true,
+            alignment: AlignmentMode::Global,
+            // BLAST's defaults for BLOSUM62
+            scoring_matrix: ScoringMatrix::Blosum62,
+            gap_open: 11,
+            gap_extend: 1,
+            effect_colors: EffectColors::default(),
+            min_base_quality: 0,
+            uncertain_color: Some([0.7, 0.7, 0.9]),
+            region: None,
+            indexed: false,
+            layout: VertexLayout::default(),
+            cross_sections: CrossSections::default(),
+            path: PathOptions::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Reads options from JSON. An empty string gives the defaults.
+    pub fn from_json(json: &str) -> Result<Options>
+    {
+        if json.trim().is_empty() {
+            return Ok(Options::default());
+        }
+        serde_json::from_str(json).map_err(|e| Error::InvalidOptions { message: e.to_string() })
+    }
+
+    pub fn genetic_code(&self) -> Result<&'static GeneticCode>
+    {
+        GeneticCode::from_id(self.genetic_code).ok_or(Error::UnknownGeneticCode { id: self.genetic_code })
+    }
+
+    pub fn scoring(&self) -> Scoring
+    {
+        Scoring { matrix: self.scoring_matrix, gap_open: self.gap_open, gap_extend: self.gap_extend }
+    }
+}
@@ -0,0 +1,264 @@
+//! Open reading frame finder: a start codon, then codons up to and including the first stop codon in the same frame.
+//! Coordinates are 0-based nucleotide offsets on the forward strand, whatever the strand of the ORF.
+//! Annotated coding sequences are ORFs too, and may be spliced from several segments.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::frame::{Frame, Strand};
+use crate::genetic_code::GeneticCode;
+use crate::input::Slice;
+use crate::nucleotide::Nucleotide;
+use crate::amino_acid::AminoAcid;
+use crate::Options;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Orf {
+    /// ID of the record the ORF was found in.
+    pub record: String,
+    pub frame: Frame,
+    /// First base of the ORF on the forward strand: the start codon, or the stop codon on the reverse strand.
+    pub start: usize,
+    /// One past the last base of the ORF on the forward strand.
+    pub end: usize,
+    /// Translated residues, from the start codon (read as Met, unless the ORF is a CDS cut off at its 5' end) up to
+    /// the stop codon, which is left out.
+    pub protein: Vec<AminoAcid>,
+    /// The codon of each residue of `protein`.
+    pub codons: Vec<[Nucleotide; 3]>,
+    /// Forward-strand ranges read, in reading order: just `start..end` for an ORF found by the finder, several
+    /// ranges for a spliced or frameshifted CDS (e.g. ORF1ab, whose second range starts one base back).
+    pub segments: Vec<Range<usize>>,
+    /// Gene name from the annotation.
+    pub name: Option<String>,
+}
+
+impl Orf {
+    pub fn strand(&self) -> Strand
+    {
+        self.frame.strand
+    }
+
+    /// Length in nucleotides, stop codon included.
+    pub fn length(&self) -> usize
+    {
+        self.end - self.start
+    }
+
+    /// Moves an ORF found in a record that only holds `slice` of a sequence to the coordinates of the whole one.
+    pub fn move_to(&mut self, slice: &Slice)
+    {
+        let offset = slice.range.start;
+        self.frame = slice.frame(self.frame);
+        self.start += offset;
+        self.end += offset;
+        for segment in &mut self.segments {
+            *segment = segment.start + offset..segment.end + offset;
+        }
+    }
+
+    pub fn overlaps(&self, other: &Orf) -> bool
+    {
+        self.record == other.record && self.start < other.end && other.start < self.end
+    }
+
+    /// Offset along the coding sequence of a forward-strand base, if the ORF reads it. A base read twice by
+    /// a frameshifted CDS gives its first offset.
+    pub fn coding_offset(&self, position: usize) -> Option<usize>
+    {
+        let mut offset = 0;
+        for segment in &self.segments {
+            if segment.contains(&position) {
+                return Some(offset + match self.strand() {
+                    Strand::Forward => position - segment.start,
+                    Strand::Reverse => segment.end - 1 - position,
+                });
+            }
+            offset += segment.len();
+        }
+        None
+    }
+
+    /// Forward-strand base at `offset` along the coding sequence: the inverse of coding_offset.
+    pub fn position_at(&self, offset: usize) -> Option<usize>
+    {
+        let mut offset = offset;
+        for segment in &self.segments {
+            if offset < segment.len() {
+                return Some(match self.strand() {
+                    Strand::Forward => segment.start + offset,
+                    Strand::Reverse => segment.end - 1 - offset,
+                });
+            }
+            offset -= segment.len();
+        }
+        None
+    }
+
+    /// Forward-strand range of the codon of residue `index`.
+    pub fn codon_range(&self, index: usize) -> Option<Range<usize>>
+    {
+        let positions: Vec<usize> = (3 * index..3 * index + 3).filter_map(|offset| self.position_at(offset)).collect();
+        Some(*positions.iter().min()?..*positions.iter().max()? + 1)
+    }
+}
+
+/// The bases of `segments`, in order, read on `strand`: reverse-complemented on the reverse strand.
+pub fn splice(bases: &[Nucleotide], segments: &[Range<usize>], strand: Strand) -> Vec<Nucleotide>
+{
+    let mut spliced: Vec<Nucleotide> = Vec::new();
+    for segment in segments {
+        let segment = &bases[segment.start.min(bases.len())..segment.end.min(bases.len())];
+        match strand {
+            Strand::Forward => spliced.extend_from_slice(segment),
+            Strand::Reverse => spliced.extend(segment.iter().rev().map(|n| n.complement())),
+        }
+    }
+    spliced
+}
+
+/// The ORF read from annotated `segments` of `bases` (see Orf::segments), up to its first stop codon or to the
+/// end of the segments. With `from_start`, the first codon is read as Met, whatever start codon it is; a CDS
+/// whose 5' end is cut off, e.g. on a read that starts inside it, is translated as it is.
+pub fn from_segments(record: &str, bases: &[Nucleotide], segments: Vec<Range<usize>>, strand: Strand, from_start: bool, code: &GeneticCode, name: Option<String>) -> Orf
+{
+    let coding = splice(bases, &segments, strand);
+    let mut codons: Vec<[Nucleotide; 3]> = Vec::new();
+    let mut protein: Vec<AminoAcid> = Vec::new();
+    for c in coding.chunks_exact(3) {
+        let codon = [c[0], c[1], c[2]];
+        let aa = code.translate(codon);
+        if aa == AminoAcid::Stop {
+            break;
+        }
+        codons.push(codon);
+        protein.push(aa);
+    }
+    if let (true, Some(first)) = (from_start, protein.first_mut()) {
+        *first = AminoAcid::Met;
+    }
+
+    let start = segments.iter().map(|s| s.start).min().unwrap_or(0);
+    let end = segments.iter().map(|s| s.end).max().unwrap_or(0);
+    let first = segments.first().cloned().unwrap_or(0..0);
+    let offset = match strand {
+        Strand::Forward => first.start % 3,
+        Strand::Reverse => (bases.len() - first.end.min(bases.len())) % 3,
+    };
+    let frame = Frame { strand, offset: offset as u8 };
+    Orf { record: record.to_string(), frame, start, end, protein, codons, segments, name }
+}
+
+/// ORFs of one frame of `seq`, in reading order. Whitespace in `seq` is skipped and does not count in coordinates.
+///
+/// Within an ORF, further start codons only open ORFs of their own with `options.nested_orfs`; these nested ORFs
+/// share the stop codon of the enclosing one. ORFs shorter than `options.min_orf_length` residues are dropped.
+pub fn find_orfs(record: &str, seq: &str, frame: Frame, code: &GeneticCode, options: &Options) -> Vec<Orf>
+{
+    let bases: String = seq.chars().filter(|c| !c.is_whitespace()).collect();
+    let length = bases.chars().count();
+    let frame_bases: Vec<Nucleotide> = frame.bases(&bases).chars()
+        .map(|c| Nucleotide::from_char(c).unwrap_or(Nucleotide::N))
+        .collect();
+
+    let codons: Vec<[Nucleotide; 3]> = frame_bases.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+    let residues: Vec<AminoAcid> = codons.iter().map(|codon| code.translate(*codon)).collect();
+
+    let mut orfs: Vec<Orf> = Vec::new();
+    // codon index of the start of every ORF still waiting for its stop codon
+    let mut open: Vec<usize> = Vec::new();
+    for (i, codon) in codons.iter().enumerate() {
+        if residues[i] == AminoAcid::Stop {
+            for &first in &open {
+                let mut protein = residues[first..i].to_vec();
+                protein[0] = AminoAcid::Met;
+                if protein.len() < options.min_orf_length {
+                    continue;
+                }
+
+                // position along the strand that was read, then back to forward strand coordinates
+                let strand_start = frame.offset as usize + 3 * first;
+                let strand_end = frame.offset as usize + 3 * (i + 1);
+                let (start, end) = match frame.strand {
+                    Strand::Forward => (strand_start, strand_end),
+                    Strand::Reverse => (length - strand_end, length - strand_start),
+                };
+                let orf_codons = codons[first..i].to_vec();
+                orfs.push(Orf {
+                    record: record.to_string(),
+                    frame,
+                    start,
+                    end,
+                    protein,
+                    codons: orf_codons,
+                    segments: std::iter::once(start..end).collect(),
+                    name: None,
+                });
+            }
+            open.clear();
+        }
+        else if code.is_start(*codon, options.alternative_starts) && (open.is_empty() || options.nested_orfs) {
+            open.push(i);
+        }
+    }
+
+    orfs
+}
+
+/// ORFs of several frames of `seq`, frame by frame. Without `options.overlapping_orfs`, an ORF that overlaps a
+/// longer ORF of another frame is dropped; of two overlapping ORFs of the same length, the one whose frame comes
+/// first in Frame::ALL (+1 .. +3, then -1 .. -3) is kept.
+pub fn find_orfs_in_frames(record: &str, seq: &str, frames: &[Frame], code: &GeneticCode, options: &Options) -> Vec<Orf>
+{
+    let orfs: Vec<Orf> = frames.iter().flat_map(|frame| find_orfs(record, seq, *frame, code, options)).collect();
+    if options.overlapping_orfs {
+        return orfs;
+    }
+
+    // true if `a` is kept over `b` when they overlap
+    let beats = |a: &Orf, b: &Orf| a.length() > b.length() || (a.length() == b.length() && a.frame.index() < b.frame.index());
+
+    let longest = orfs.iter().map(|orf| orf.length()).max().unwrap_or(0);
+    let mut by_rank: Vec<usize> = (0..orfs.len()).collect();
+    by_rank.sort_by_key(|&i| (std::cmp::Reverse(orfs[i].length()), orfs[i].frame.index()));
+
+    // kept ORFs by start position; none is longer than `longest`, which bounds how far back an overlap can start
+    let mut kept_by_start: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut kept = vec![false; orfs.len()];
+    for &i in &by_rank {
+        let orf = &orfs[i];
+        let blocked = kept_by_start.range(orf.start.saturating_sub(longest)..orf.end)
+            .flat_map(|(_, ids)| ids)
+            .any(|&j| orfs[j].frame != orf.frame && orfs[j].overlaps(orf) && beats(&orfs[j], orf));
+        if !blocked {
+            kept[i] = true;
+            kept_by_start.entry(orf.start).or_default().push(i);
+        }
+    }
+
+    orfs.into_iter().zip(kept).filter(|(_, keep)| *keep).map(|(orf, _)| orf).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_code::STANDARD;
+
+    #[test]
+    fn equal_overlapping_orfs_keep_the_earlier_frame()
+    {
+        // ATG AAT GAA TAA in +1 (0..12), ATG AAT AAG TAA in +2 (4..16)
+        let seq = "ATGAATGAATAAGTAA";
+        let options = Options { overlapping_orfs: false, ..Options::default() };
+        let frames = [Frame::ALL[1], Frame::ALL[0]];
+        let orfs = find_orfs_in_frames("r", seq, &frames, &STANDARD, &options);
+        assert_eq!(orfs.iter().map(|orf| (orf.frame, orf.start..orf.end)).collect::<Vec<_>>(), [(Frame::ALL[0], 0..12)]);
+
+        // one codon longer, the +2 ORF wins
+        let seq = "ATGAATGAATAAGCCCTAA";
+        let orfs = find_orfs_in_frames("r", seq, &frames, &STANDARD, &options);
+        assert_eq!(orfs.iter().map(|orf| (orf.frame, orf.start..orf.end)).collect::<Vec<_>>(), [(Frame::ALL[1], 4..19)]);
+    }
+}
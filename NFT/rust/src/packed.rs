@@ -0,0 +1,143 @@
+//! Compact nucleotide store: four bases per byte, with the runs of unknown bases kept aside as an N-mask.
+//! Ambiguity codes other than N cannot be packed and are stored as N. The packing is that of UCSC .2bit files,
+//! so their sequence data is taken as it is (see twobit).
+
+use std::ops::Range;
+
+use crate::nucleotide::Nucleotide;
+
+// 2-bit code of each base, in .2bit order
+const CODES: [Nucleotide; 4] = [Nucleotide::T, Nucleotide::C, Nucleotide::A, Nucleotide::G];
+
+fn code(base: Nucleotide) -> u8
+{
+    match base {
+        Nucleotide::T => 0,
+        Nucleotide::C => 1,
+        Nucleotide::A => 2,
+        Nucleotide::G => 3,
+        // masked as N anyway
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PackedSequence {
+    length: usize,
+    /// Base i is in the bits 7-2(i%4)..6-2(i%4) of byte i/4: the first base is in the high bits.
+    bytes: Vec<u8>,
+    /// Sorted, disjoint runs of N.
+    n_blocks: Vec<Range<usize>>,
+}
+
+impl PackedSequence {
+    /// Packs `bases`, which are read as in FASTA: whitespace is skipped and unknown characters are N.
+    pub fn from_text(bases: &str) -> PackedSequence
+    {
+        let mut packed = PackedSequence::default();
+        for c in bases.chars().filter(|c| !c.is_whitespace()) {
+            packed.push(Nucleotide::from_char(c).unwrap_or(Nucleotide::N));
+        }
+        packed
+    }
+
+    /// A sequence from .2bit data: `length` bases packed in `bytes`, with runs of N in `n_blocks`.
+    pub fn from_parts(length: usize, bytes: Vec<u8>, mut n_blocks: Vec<Range<usize>>) -> PackedSequence
+    {
+        n_blocks.retain(|block| !block.is_empty());
+        n_blocks.sort_by_key(|block| block.start);
+        PackedSequence { length, bytes, n_blocks }
+    }
+
+    /// Length, packed bytes and N-mask runs, as from_parts takes them.
+    pub fn into_parts(self) -> (usize, Vec<u8>, Vec<Range<usize>>)
+    {
+        (self.length, self.bytes, self.n_blocks)
+    }
+
+    pub fn push(&mut self, base: Nucleotide)
+    {
+        let i = self.length;
+        if i.is_multiple_of(4) {
+            self.bytes.push(0);
+        }
+        self.bytes[i / 4] |= code(base) << (6 - 2 * (i % 4));
+        if base.is_ambiguous() {
+            match self.n_blocks.last_mut() {
+                Some(block) if block.end == i => block.end += 1,
+                _ => self.n_blocks.push(i..i + 1),
+            }
+        }
+        self.length += 1;
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.length == 0
+    }
+
+    /// Bytes used by the bases and the mask, for a sense of how compact the store is.
+    pub fn size(&self) -> usize
+    {
+        self.bytes.len() + self.n_blocks.len() * std::mem::size_of::<Range<usize>>()
+    }
+
+    // N-mask runs that overlap `range`
+    fn n_blocks_in(&self, range: &Range<usize>) -> &[Range<usize>]
+    {
+        let first = self.n_blocks.partition_point(|block| block.end <= range.start);
+        let last = self.n_blocks.partition_point(|block| block.start < range.end);
+        &self.n_blocks[first..last.max(first)]
+    }
+
+    /// The base at `index`; panics past the end, as slices do.
+    pub fn get(&self, index: usize) -> Nucleotide
+    {
+        assert!(index < self.length, "base {} out of a sequence of {}", index, self.length);
+        if !self.n_blocks_in(&(index..index + 1)).is_empty() {
+            return Nucleotide::N;
+        }
+        CODES[((self.bytes[index / 4] >> (6 - 2 * (index % 4))) & 3) as usize]
+    }
+
+    /// The bases of `range`, clamped to the sequence, decoded without touching the rest.
+    pub fn slice(&self, range: Range<usize>) -> Vec<Nucleotide>
+    {
+        let range = range.start.min(self.length)..range.end.min(self.length);
+        let mut bases: Vec<Nucleotide> = range.clone()
+            .map(|i| CODES[((self.bytes[i / 4] >> (6 - 2 * (i % 4))) & 3) as usize])
+            .collect();
+        for block in self.n_blocks_in(&range) {
+            for i in block.start.max(range.start)..block.end.min(range.end) {
+                bases[i - range.start] = Nucleotide::N;
+            }
+        }
+        bases
+    }
+
+    /// The bases of `range` as text, e.g. for a FASTA record.
+    pub fn slice_text(&self, range: Range<usize>) -> String
+    {
+        self.slice(range).into_iter().map(|base| base.to_char()).collect()
+    }
+
+    /// A new packed sequence of the bases of `range`.
+    pub fn subsequence(&self, range: Range<usize>) -> PackedSequence
+    {
+        let mut packed = PackedSequence::default();
+        for base in self.slice(range) {
+            packed.push(base);
+        }
+        packed
+    }
+
+    pub fn to_text(&self) -> String
+    {
+        self.slice_text(0..self.length)
+    }
+}
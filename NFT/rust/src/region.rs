@@ -0,0 +1,153 @@
+//! Region of the reference to draw: a coordinate range such as "MN908947.3:21,563-25,384" or a gene name from
+//! the annotation, such as "S". Only the proteins that overlap it are drawn; coordinates stay those of the genome.
+
+use std::ops::Range;
+
+use crate::annotation::{self, Feature};
+use crate::fasta::Record;
+use crate::input::Slice;
+use crate::variant::Pairing;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Index of the reference record the region is on.
+    pub index: usize,
+    /// 0-based forward-strand range.
+    pub range: Range<usize>,
+}
+
+fn invalid(region: &str, message: &str) -> Error
+{
+    Error::InvalidRegion { region: region.to_string(), message: message.to_string() }
+}
+
+// "21,563" -> 21563
+fn coordinate(text: &str) -> Option<usize>
+{
+    text.trim().replace(',', "").parse().ok()
+}
+
+// "chr:start-end" or "start-end", 1-based and inclusive; None if `text` is not coordinates
+fn coordinates(text: &str) -> Option<(Option<&str>, usize, usize)>
+{
+    // record names may hold ':' themselves, the range never does
+    let (record, range) = match text.rsplit_once(':') {
+        Some((record, range)) => (Some(record), range),
+        None => (None, text),
+    };
+    let (start, end) = range.split_once('-')?;
+    Some((record, coordinate(start)?, coordinate(end)?))
+}
+
+/// Finds `text` among the reference records, or among the gene names of `features`. Without a record name, the
+/// reference must be a single record.
+pub fn resolve(text: &str, features: &[Feature], references: &[Record]) -> Result<Region>
+{
+    let records: Vec<(String, usize)> = references.iter().map(|r| (r.id.clone(), r.sequence.len())).collect();
+    resolve_among(text, features, &records)
+}
+
+/// Same as resolve, among records given by ID and length, e.g. the sequences of a .2bit file before any is read.
+pub fn resolve_among(text: &str, features: &[Feature], references: &[(String, usize)]) -> Result<Region>
+{
+    let lone = references.len() == 1;
+    let index_of = |record: Option<&str>| match record {
+        Some(record) => match references.iter().position(|(id, _)| id == record) {
+            Some(index) => Ok(index),
+            // a lone unnamed reference takes every name, as for annotations and VCF files
+            None if lone && references[0].0.is_empty() => Ok(0),
+            None => Err(Error::UnknownRecord { record: record.to_string() }),
+        },
+        None if lone => Ok(0),
+        None => Err(invalid(text, "name the record, e.g. chr1:100-200: the reference has several")),
+    };
+
+    let text = text.trim();
+    if let Some((record, start, end)) = coordinates(text) {
+        if start == 0 || end < start {
+            return Err(invalid(text, "expected 1-based start-end with start <= end"));
+        }
+        let index = index_of(record)?;
+        let length = references[index].1;
+        if start > length {
+            return Err(invalid(text, &format!("starts past the end of the record, which has {} bases", length)));
+        }
+        return Ok(Region { index, range: start - 1..end.min(length) });
+    }
+
+    let gene = features.iter().find(|f| f.is_cds() && f.name.as_deref() == Some(text));
+    match gene {
+        Some(feature) => {
+            let start = feature.segments.iter().map(|s| s.start).min().unwrap_or(0);
+            let end = feature.segments.iter().map(|s| s.end).max().unwrap_or(0);
+            Ok(Region { index: index_of(Some(&feature.record))?, range: start..end })
+        }
+        None => Err(Error::UnknownGene { gene: text.to_string() }),
+    }
+}
+
+impl Region {
+    /// True if `range` of the record `record` overlaps the region; `records` are those the region is on.
+    pub fn overlaps(&self, records: &[Record], record: &str, range: &Range<usize>) -> bool
+    {
+        records[self.index].id == record && range.start < self.range.end && self.range.start < range.end.max(range.start + 1)
+    }
+
+    /// The region in the coordinates of a record that only holds `slice` of the sequence, clamped to it.
+    pub fn within(&self, slice: &Slice) -> Region
+    {
+        let local = |position: usize| position.clamp(slice.range.start, slice.range.end) - slice.range.start;
+        Region { index: self.index, range: local(self.range.start)..local(self.range.end) }
+    }
+
+    /// The same region on each sample record paired with the reference record, through their alignment, e.g. on
+    /// every read that covers it.
+    pub fn in_sample(&self, records: &[Record], references: &[Record], pairings: &[Pairing]) -> Vec<Region>
+    {
+        pairings.iter()
+            .filter(|p| p.reference == self.index)
+            .map(|pairing| {
+                let sample = &records[pairing.sample];
+                let positions = annotation::sample_positions(&pairing.alignment, references[self.index].sequence.len(), sample.sequence.len());
+                let range = pairing.on_sample(positions[self.range.start]..positions[self.range.end], sample.sequence.len());
+                Region { index: pairing.sample, range }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<(String, usize)>
+    {
+        vec![("chr1".to_string(), 1000), ("chr2:alt".to_string(), 500)]
+    }
+
+    #[test]
+    fn coordinates_and_genes()
+    {
+        let features = annotation::parse("chr2:alt\t.\tCDS\t101\t160\t.\t-\t0\tgene=g\n").unwrap();
+        let resolve = |text: &str| resolve_among(text, &features, &records());
+        assert!(matches!(resolve("chr1:1,001-1,200"), Err(Error::InvalidRegion { .. })));
+        assert_eq!(resolve("chr1:101-1,200").unwrap(), Region { index: 0, range: 100..1000 });
+        assert_eq!(resolve("chr2:alt:1-10").unwrap(), Region { index: 1, range: 0..10 });
+        assert_eq!(resolve("g").unwrap(), Region { index: 1, range: 100..160 });
+        assert!(matches!(resolve("1-10"), Err(Error::InvalidRegion { .. })));
+        assert!(matches!(resolve("chr3:1-10"), Err(Error::UnknownRecord { .. })));
+        assert!(matches!(resolve("chr1:20-10"), Err(Error::InvalidRegion { .. })));
+        assert!(matches!(resolve("h"), Err(Error::UnknownGene { .. })));
+        // a lone reference needs no name
+        assert_eq!(resolve_among("5-9", &[], &records()[..1]).unwrap(), Region { index: 0, range: 4..9 });
+    }
+
+    #[test]
+    fn within_a_slice()
+    {
+        let region = Region { index: 0, range: 100..300 };
+        let slice = Slice { range: 200..1000, length: 5000 };
+        assert_eq!(region.within(&slice), Region { index: 0, range: 0..100 });
+    }
+}
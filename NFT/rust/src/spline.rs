@@ -0,0 +1,156 @@
+//! Path of a strip through its residues: a cubic spline through the points the residues end at, with the same
+//! tangent on both sides of each point, so the path has no kink where two residues meet (C1). Each residue is a
+//! cubic Hermite segment, which is the cubic Bézier curve with control points p0 + m0 / 3 and p1 - m1 / 3.
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Curve {
+    /// Catmull-Rom: the tangent at a point is that of the chord between its two neighbours.
+    CatmullRom,
+    /// Bézier segments with their control points along the heading of each residue, so the path leaves a residue
+    /// in the direction it turned to.
+    Bezier,
+}
+
+/// How the path is drawn; part of the load options.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PathOptions {
+    pub curve: Curve,
+    /// Tangents are scaled by 1 - tension: 0 is the plain curve, 1 gives straight segments.
+    pub tension: f32,
+    /// Rows per residue, relative to those of its shape (2 on a straight residue, 6 on a turn).
+    pub density: f32,
+}
+
+impl Default for PathOptions {
+    fn default() -> PathOptions
+    {
+        PathOptions { curve: Curve::CatmullRom, tension: 0.0, density: 1.0 }
+    }
+}
+
+impl PathOptions {
+    /// Rows of a residue drawn with `division` rows at density 1, at least 1.
+    pub fn rows(&self, division: i32) -> i32
+    {
+        ((division as f32 * self.density).round() as i32).max(1)
+    }
+}
+
+/// Tangent of the spline at each of `knots`, where the path heads along the unit vectors `headings`.
+pub fn tangents(knots: &[glm::Vec3], headings: &[glm::Vec3], path: &PathOptions) -> Vec<glm::Vec3>
+{
+    let count = knots.len();
+    let scale = 1.0 - path.tension;
+    (0..count)
+        .map(|k| {
+            let before = knots[k.saturating_sub(1)];
+            let after = knots[(k + 1).min(count - 1)];
+            match path.curve {
+                Curve::CatmullRom => {
+                    // the end points have a single neighbour
+                    let span = if k == 0 || k == count - 1 { 1.0 } else { 0.5 };
+                    scale * span * (after - before)
+                }
+                Curve::Bezier => {
+                    // as long as the residues around the point, on average, for the same speed on both sides
+                    let chords = [glm::distance(&before, &knots[k]), glm::distance(&knots[k], &after)];
+                    let sides = (k > 0) as usize + (k + 1 < count) as usize;
+                    scale * (chords[0] + chords[1]) / sides.max(1) as f32 * headings[k]
+                }
+            }
+        })
+        .collect()
+}
+
+/// The point a fraction `t` of the way along the segment from `p0` to `p1`, with tangents `m0` and `m1`.
+pub fn hermite(p0: &glm::Vec3, m0: &glm::Vec3, p1: &glm::Vec3, m1: &glm::Vec3, t: f32) -> glm::Vec3
+{
+    let (t2, t3) = (t * t, t * t * t);
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0 + (t3 - 2.0 * t2 + t) * m0 + (3.0 * t2 - 2.0 * t3) * p1 + (t3 - t2) * m1
+}
+
+/// The derivative of hermite along `t`: the tangent at that point.
+pub fn hermite_derivative(p0: &glm::Vec3, m0: &glm::Vec3, p1: &glm::Vec3, m1: &glm::Vec3, t: f32) -> glm::Vec3
+{
+    let t2 = t * t;
+    (6.0 * t2 - 6.0 * t) * p0 + (3.0 * t2 - 4.0 * t + 1.0) * m0 + (6.0 * t - 6.0 * t2) * p1 + (3.0 * t2 - 2.0 * t) * m1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn knots() -> Vec<glm::Vec3>
+    {
+        vec![glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 0.5, 0.0), glm::vec3(1.5, 2.0, 0.5), glm::vec3(0.5, 3.0, 1.0), glm::vec3(-1.0, 3.5, 1.0)]
+    }
+
+    fn headings() -> Vec<glm::Vec3>
+    {
+        knots().windows(2).map(|pair| glm::normalize(&(pair[1] - pair[0]))).chain([glm::vec3(-1.0, 0.0, 0.0)]).collect()
+    }
+
+    fn path(curve: Curve, tension: f32) -> PathOptions
+    {
+        PathOptions { curve, tension, ..PathOptions::default() }
+    }
+
+    #[test]
+    fn segments_meet_with_the_same_tangent()
+    {
+        let knots = knots();
+        for curve in [Curve::CatmullRom, Curve::Bezier] {
+            let m = tangents(&knots, &headings(), &path(curve, 0.2));
+            let at = |i: usize, t: f32| {
+                let (p0, m0, p1, m1) = (&knots[i], &m[i], &knots[i + 1], &m[i + 1]);
+                (hermite(p0, m0, p1, m1, t), hermite_derivative(p0, m0, p1, m1, t))
+            };
+            for k in 1..knots.len() - 1 {
+                // the end of the segment before the knot, and the start of the one after it
+                let (end, start) = (at(k - 1, 1.0), at(k, 0.0));
+                assert!(glm::distance(&end.0, &knots[k]) < 1e-6 && glm::distance(&start.0, &knots[k]) < 1e-6);
+                assert!(glm::distance(&end.1, &start.1) < 1e-5, "{:?} at knot {}", curve, k);
+                assert!(glm::distance(&end.1, &m[k]) < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn full_tension_draws_straight_segments()
+    {
+        let knots = knots();
+        for curve in [Curve::CatmullRom, Curve::Bezier] {
+            let m = tangents(&knots, &headings(), &path(curve, 1.0));
+            assert!(m.iter().all(|tangent| *tangent == glm::vec3(0.0, 0.0, 0.0)));
+            for t in [0.25, 0.5, 0.75] {
+                let point = hermite(&knots[1], &m[1], &knots[2], &m[2], t);
+                let (chord, offset) = (knots[2] - knots[1], point - knots[1]);
+                assert!(glm::length(&glm::cross(&chord, &offset)) < 1e-5, "{:?} off the chord at {}", curve, t);
+            }
+        }
+    }
+
+    #[test]
+    fn catmull_rom_tangents()
+    {
+        let knots = knots();
+        let m = tangents(&knots, &headings(), &PathOptions::default());
+        // the ends only have one neighbour; the other points take half the chord between their two
+        assert_eq!(m[0], knots[1] - knots[0]);
+        assert_eq!(m[4], knots[4] - knots[3]);
+        assert_eq!(m[2], 0.5 * (knots[3] - knots[1]));
+        assert_eq!(tangents(&knots[..1], &headings()[..1], &PathOptions::default()), vec![glm::vec3(0.0, 0.0, 0.0)]);
+    }
+
+    #[test]
+    fn at_least_one_row()
+    {
+        let rows = |density: f32, division: i32| PathOptions { density, ..PathOptions::default() }.rows(division);
+        assert_eq!((rows(1.0, 2), rows(1.0, 6), rows(2.5, 2)), (2, 6, 5));
+        assert_eq!((rows(0.0, 6), rows(0.1, 2), rows(-1.0, 6), rows(1.0, 0)), (1, 1, 1, 1));
+    }
+}
@@ -0,0 +1,33 @@
+//! Fixtures shared by the unit tests: pseudo-random sequences, the same for a seed on every run.
+
+use crate::amino_acid::AminoAcid;
+use crate::fasta::Record;
+
+// pseudo-random numbers from `seed`, as the C library's rand() draws them
+fn draws(seed: u32) -> impl Iterator<Item = usize>
+{
+    let mut state = seed;
+    std::iter::repeat_with(move || {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as usize
+    })
+}
+
+/// A genome of `length` pseudo-random bases.
+pub fn genome(length: usize, seed: u32) -> String
+{
+    draws(seed).take(length).map(|draw| ['A', 'C', 'G', 'T'][draw % 4]).collect()
+}
+
+/// A protein of `length` pseudo-random residues, with no stop.
+pub fn protein(length: usize, seed: u32) -> Vec<AminoAcid>
+{
+    let letters: Vec<char> = "ARNDCQEGHILKMFPSTWYV".chars().collect();
+    draws(seed).take(length).map(|draw| AminoAcid::from_one_letter(letters[draw % 20]).unwrap()).collect()
+}
+
+/// A record with no description.
+pub fn record(id: &str, sequence: &str) -> Record
+{
+    Record { id: id.to_string(), description: String::new(), sequence: sequence.to_string() }
+}
@@ -0,0 +1,159 @@
+//! Rotation-minimizing (parallel transport) frames along the path of a strip: the normal is carried from one
+//! direction to the next with the smallest rotation, so the ribbon never spins on its own; the twists of the
+//! residues are applied on top of it. Every normalization has a fallback, so degenerate input gives no NaN.
+
+/// `v` scaled to length 1, or `fallback` if `v` is too short (or not finite) to have a direction.
+pub fn safe_normalize(v: &glm::Vec3, fallback: &glm::Vec3) -> glm::Vec3
+{
+    // below the smallest normal f32, the squared length has lost its precision to underflow. A tolerance relative
+    // to 1, such as f32::EPSILON, would not do: residues shrink geometrically along a strip, so the chords of a
+    // long protein are far shorter than that and still have a direction
+    let squared = glm::dot(v, v);
+    if squared >= f32::MIN_POSITIVE && squared.is_finite() {
+        v / squared.sqrt()
+    }
+    else {
+        *fallback
+    }
+}
+
+/// Some unit vector perpendicular to `v`.
+pub fn perpendicular(v: &glm::Vec3) -> glm::Vec3
+{
+    // cross with the axis `v` is the least along
+    let axis = if v.x.abs() <= v.y.abs() && v.x.abs() <= v.z.abs() {
+        glm::vec3(1.0, 0.0, 0.0)
+    }
+    else if v.y.abs() <= v.z.abs() {
+        glm::vec3(0.0, 1.0, 0.0)
+    }
+    else {
+        glm::vec3(0.0, 0.0, 1.0)
+    };
+    safe_normalize(&glm::cross(v, &axis), &glm::vec3(0.0, 0.0, 1.0))
+}
+
+/// `normal` made perpendicular to the unit vector `tangent`, and of length 1.
+pub fn orthonormalize(normal: &glm::Vec3, tangent: &glm::Vec3) -> glm::Vec3
+{
+    safe_normalize(&(normal - glm::dot(normal, tangent) * tangent), &perpendicular(tangent))
+}
+
+/// `normal` rotated by `angle` radians around the unit vector `axis`, right-handed.
+pub fn twist(normal: &glm::Vec3, axis: &glm::Vec3, angle: f32) -> glm::Vec3
+{
+    let (sin, cos) = angle.sin_cos();
+    cos * normal + sin * glm::cross(axis, normal) + (1.0 - cos) * glm::dot(axis, normal) * axis
+}
+
+/// The normal `normal` of the unit direction `from` carried over to the unit direction `to`, by the smallest
+/// rotation that turns one direction into the other. A U-turn has no smallest rotation: it turns around the normal.
+pub fn transport(normal: &glm::Vec3, from: &glm::Vec3, to: &glm::Vec3) -> glm::Vec3
+{
+    let cos = glm::dot(from, to).clamp(-1.0, 1.0);
+    let axis = safe_normalize(&glm::cross(from, to), &orthonormalize(normal, from));
+    orthonormalize(&twist(normal, &axis, cos.acos()), to)
+}
+
+/// Angle in radians, around the unit vector `axis`, from `from` to `to`, both perpendicular to it.
+pub fn signed_angle(from: &glm::Vec3, to: &glm::Vec3, axis: &glm::Vec3) -> f32
+{
+    glm::dot(&glm::cross(from, to), axis).atan2(glm::dot(from, to))
+}
+
+/// The unit direction a fraction `alpha` of the way from `from` to `to`, turning at a constant rate.
+pub fn slerp(from: &glm::Vec3, to: &glm::Vec3, alpha: f32) -> glm::Vec3
+{
+    let cos = glm::dot(from, to).clamp(-1.0, 1.0);
+    let axis = safe_normalize(&glm::cross(from, to), &perpendicular(from));
+    safe_normalize(&twist(from, &axis, alpha * cos.acos()), from)
+}
+
+/// Normals along a path of unit `tangents`, from `start_normal` to `end_normal`: each one is carried over from
+/// the one before, and the twist left between the last one and `end_normal` is spread evenly along the path.
+pub fn frames(tangents: &[glm::Vec3], start_normal: &glm::Vec3, end_normal: &glm::Vec3) -> Vec<glm::Vec3>
+{
+    let mut normals: Vec<glm::Vec3> = Vec::with_capacity(tangents.len());
+    for (i, tangent) in tangents.iter().enumerate() {
+        let normal = match i {
+            0 => orthonormalize(start_normal, tangent),
+            _ => transport(&normals[i - 1], &tangents[i - 1], tangent),
+        };
+        normals.push(normal);
+    }
+    let last = match tangents.len() {
+        0 | 1 => return normals,
+        count => count - 1,
+    };
+    let residual = signed_angle(&normals[last], &orthonormalize(end_normal, &tangents[last]), &tangents[last]);
+    for (i, normal) in normals.iter_mut().enumerate() {
+        *normal = orthonormalize(&twist(normal, &tangents[i], residual * i as f32 / last as f32), &tangents[i]);
+    }
+    normals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_unit(v: &glm::Vec3) -> bool
+    {
+        v.iter().all(|x| x.is_finite()) && (glm::length(v) - 1.0).abs() < 1e-5
+    }
+
+    // unit directions around a circle in the xy plane
+    fn circle(count: usize) -> Vec<glm::Vec3>
+    {
+        (0..count).map(|i| {
+            let (sin, cos) = (0.3 * i as f32).sin_cos();
+            glm::vec3(cos, sin, 0.0)
+        }).collect()
+    }
+
+    #[test]
+    fn planar_curve_keeps_normals_in_plane()
+    {
+        let tangents = circle(20);
+        // the binormal stays the binormal, and a normal in the plane stays in it
+        let up = glm::vec3(0.0, 0.0, 1.0);
+        let across = glm::vec3(0.0, 1.0, 0.0);
+        let (mut binormal, mut normal) = (up, across);
+        for pair in tangents.windows(2) {
+            binormal = transport(&binormal, &pair[0], &pair[1]);
+            normal = transport(&normal, &pair[0], &pair[1]);
+            assert!(glm::distance(&binormal, &up) < 1e-5);
+            assert!(normal.z.abs() < 1e-5 && is_unit(&normal));
+            assert!(glm::dot(&normal, &pair[1]).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn degenerate_directions_give_unit_normals()
+    {
+        let (x, z) = (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0));
+        let zero = glm::vec3(0.0, 0.0, 0.0);
+        // a U-turn turns around the normal, which it keeps
+        let turned = transport(&z, &x, &-x);
+        assert!(is_unit(&turned) && glm::distance(&turned, &z) < 1e-5);
+        for normal in [transport(&z, &zero, &x), transport(&zero, &x, &z), orthonormalize(&x, &x), perpendicular(&zero)] {
+            assert!(is_unit(&normal), "{:?}", normal);
+        }
+        assert_eq!(safe_normalize(&zero, &z), z);
+        assert_eq!(safe_normalize(&glm::vec3(f32::NAN, 0.0, 0.0), &z), z);
+        // short, but still a direction
+        assert!(is_unit(&safe_normalize(&glm::vec3(1e-16, 0.0, 0.0), &z)));
+    }
+
+    #[test]
+    fn frames_end_at_the_end_normal()
+    {
+        let tangents: Vec<glm::Vec3> = circle(10).iter().map(|t| glm::normalize(&(t + glm::vec3(0.0, 0.0, 0.5)))).collect();
+        let start = orthonormalize(&glm::vec3(0.0, 0.0, 1.0), &tangents[0]);
+        let last = tangents[9];
+        let end = twist(&orthonormalize(&glm::vec3(0.0, 0.0, 1.0), &last), &last, 2.0);
+        let normals = frames(&tangents, &start, &end);
+        assert!(glm::distance(&normals[0], &start) < 1e-5);
+        assert!(glm::distance(&normals[9], &end) < 1e-5, "{:?} {:?}", normals[9], end);
+        assert!(normals.iter().zip(&tangents).all(|(n, t)| is_unit(n) && glm::dot(n, t).abs() < 1e-5));
+    }
+}
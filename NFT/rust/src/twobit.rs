@@ -0,0 +1,284 @@
+//! UCSC .2bit reader (https://genome.ucsc.edu/FAQ/FAQformat.html#format7). The file stays as it is in memory;
+//! only the sequences or ranges asked for are decoded. Soft-masking (lowercase) is ignored.
+
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use crate::fasta::Record;
+use crate::packed::PackedSequence;
+use crate::{Error, Result};
+
+const SIGNATURE: u32 = 0x1A41_2743;
+
+/// True if `bytes` start with the .2bit signature, in either byte order.
+pub fn is_twobit(bytes: &[u8]) -> bool
+{
+    bytes.len() >= 4 && (bytes[..4] == SIGNATURE.to_le_bytes() || bytes[..4] == SIGNATURE.to_be_bytes())
+}
+
+fn invalid(message: String) -> Error
+{
+    Error::InvalidTwoBit { message }
+}
+
+// offsets and sizes come from the file, and may not fit in a usize on 32-bit targets such as wasm32
+fn checked(value: Option<usize>) -> Result<usize>
+{
+    value.ok_or_else(|| invalid("offset out of range".to_string()))
+}
+
+pub struct TwoBitFile<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+    /// Name and file offset of each sequence, in file order.
+    index: Vec<(String, usize)>,
+}
+
+// where the bases of a sequence are, from its record header
+struct SequenceHeader {
+    length: usize,
+    n_blocks: Vec<Range<usize>>,
+    /// File offset of the packed bases.
+    data: usize,
+}
+
+impl<'a> TwoBitFile<'a> {
+    /// Reads the header and the index of the sequences; nothing else is decoded.
+    pub fn open(bytes: &'a [u8]) -> Result<TwoBitFile<'a>>
+    {
+        if !is_twobit(bytes) {
+            return Err(invalid("missing .2bit signature".to_string()));
+        }
+        let mut file = TwoBitFile { bytes, big_endian: bytes[..4] == SIGNATURE.to_be_bytes(), index: Vec::new() };
+        let version = file.u32_at(4)?;
+        if version > 1 {
+            return Err(invalid(format!("unknown version {}", version)));
+        }
+        let count = file.u32_at(8)? as usize;
+
+        let mut offset = 16;
+        for _ in 0..count {
+            let name_size = *bytes.get(offset).ok_or_else(|| invalid("index is cut short".to_string()))? as usize;
+            let name = bytes.get(offset + 1..offset + 1 + name_size).ok_or_else(|| invalid("index is cut short".to_string()))?;
+            offset += 1 + name_size;
+            // version 1 has 64-bit offsets, for files over 4 GB
+            let position = if version == 1 {
+                let (first, second) = (file.u32_at(offset)? as u64, file.u32_at(offset + 4)? as u64);
+                let position = if file.big_endian { first << 32 | second } else { second << 32 | first };
+                offset += 8;
+                checked(usize::try_from(position).ok())?
+            }
+            else {
+                offset += 4;
+                file.u32_at(offset - 4)? as usize
+            };
+            file.index.push((String::from_utf8_lossy(name).into_owned(), position));
+        }
+        Ok(file)
+    }
+
+    fn u32_at(&self, offset: usize) -> Result<u32>
+    {
+        let end = checked(offset.checked_add(4))?;
+        let bytes = self.bytes.get(offset..end).ok_or_else(|| invalid(format!("file ends before byte {}", end)))?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    }
+
+    // `count` 32-bit values from `offset` on
+    fn u32s_at(&self, offset: usize, count: usize) -> Result<Vec<usize>>
+    {
+        // all there before anything is allocated for them
+        let end = checked(count.checked_mul(4).and_then(|size| offset.checked_add(size)))?;
+        if self.bytes.len() < end {
+            return Err(invalid(format!("file ends before byte {}", end)));
+        }
+        (0..count).map(|i| self.u32_at(offset + 4 * i).map(|value| value as usize)).collect()
+    }
+
+    /// Names of the sequences, in file order.
+    pub fn names(&self) -> impl Iterator<Item = &str>
+    {
+        self.index.iter().map(|(name, _)| name.as_str())
+    }
+
+    fn header(&self, name: &str) -> Result<SequenceHeader>
+    {
+        let offset = match self.index.iter().find(|(n, _)| n == name) {
+            Some(&(_, offset)) => offset,
+            None => return Err(Error::UnknownRecord { record: name.to_string() }),
+        };
+        let length = self.u32_at(offset)? as usize;
+        let n_count = self.u32_at(checked(offset.checked_add(4))?)? as usize;
+        let n_offset = checked(offset.checked_add(8))?;
+        let n_starts = self.u32s_at(n_offset, n_count)?;
+        // both were read, so 8 * n_count bytes are in the file
+        let n_sizes = self.u32s_at(n_offset + 4 * n_count, n_count)?;
+        let mask_offset = n_offset + 8 * n_count;
+        let mask_count = self.u32_at(mask_offset)? as usize;
+        // mask starts and sizes, then a reserved word
+        let data = checked(mask_count.checked_mul(8).and_then(|size| size.checked_add(mask_offset))
+            .and_then(|end| end.checked_add(8)))?;
+        let end = checked(data.checked_add(length.div_ceil(4)))?;
+        if self.bytes.len() < end {
+            return Err(invalid(format!("sequence '{}' is cut short", name)));
+        }
+        let n_blocks = n_starts.into_iter().zip(n_sizes)
+            .map(|(start, size)| Ok(start..checked(start.checked_add(size))?))
+            .collect::<Result<_>>()?;
+        Ok(SequenceHeader { length, n_blocks, data })
+    }
+
+    /// Number of bases of the sequence `name`.
+    pub fn length(&self, name: &str) -> Result<usize>
+    {
+        Ok(self.header(name)?.length)
+    }
+
+    /// The bases of `range` of the sequence `name`, clamped to its length. Only the bytes that hold them are read.
+    pub fn read(&self, name: &str, range: Range<usize>) -> Result<PackedSequence>
+    {
+        let header = self.header(name)?;
+        let range = range.start.min(header.length)..range.end.min(header.length).max(range.start.min(header.length));
+        let n_blocks: Vec<Range<usize>> = header.n_blocks.iter()
+            .filter(|block| block.start < range.end && range.start < block.end)
+            .map(|block| block.start.max(range.start) - range.start..block.end.min(range.end) - range.start)
+            .collect();
+        let bytes = &self.bytes[header.data + range.start / 4..header.data + range.end.div_ceil(4)];
+
+        if range.start.is_multiple_of(4) {
+            // already packed the same way: take the bytes as they are, minus the bases past the end
+            let mut bytes = bytes.to_vec();
+            if let Some(last) = bytes.last_mut() {
+                let used = (range.len() % 4) as u32;
+                if used != 0 {
+                    *last &= !(0xffu8 >> (2 * used));
+                }
+            }
+            return Ok(PackedSequence::from_parts(range.len(), bytes, n_blocks));
+        }
+        // shift the bases to the start of the first byte
+        let skip = range.start % 4;
+        let shifted = PackedSequence::from_parts(bytes.len() * 4, bytes.to_vec(), Vec::new()).subsequence(skip..skip + range.len());
+        let (length, bytes, _) = shifted.into_parts();
+        Ok(PackedSequence::from_parts(length, bytes, n_blocks))
+    }
+
+    /// The sequence `name` as a record, or only `range` of it.
+    pub fn record(&self, name: &str, range: Option<Range<usize>>) -> Result<Record>
+    {
+        let range = match range {
+            Some(range) => range,
+            None => 0..self.length(name)?,
+        };
+        Ok(Record { id: name.to_string(), description: String::new(), sequence: self.read(name, range)?.to_text() })
+    }
+
+    /// Every sequence, decoded.
+    pub fn records(&self) -> Result<Vec<Record>>
+    {
+        self.names().map(|name| self.record(name, None)).collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // runs of the characters of `sequence` for which `test` holds, as (start, length)
+    fn runs(sequence: &str, test: impl Fn(char) -> bool) -> Vec<(u32, u32)>
+    {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for (i, c) in sequence.chars().enumerate() {
+            if !test(c) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((start, length)) if *start + *length == i as u32 => *length += 1,
+                _ => runs.push((i as u32, 1)),
+            }
+        }
+        runs
+    }
+
+    /// A little-endian version 0 .2bit file of `sequences`: N (or any other code) is an N-block, lowercase is
+    /// soft-masked.
+    pub fn encode(sequences: &[(&str, &str)]) -> Vec<u8>
+    {
+        let mut header: Vec<u8> = Vec::new();
+        for value in [SIGNATURE, 0, sequences.len() as u32, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        let index_size: usize = sequences.iter().map(|(name, _)| 1 + name.len() + 4).sum();
+        let mut data: Vec<u8> = Vec::new();
+        for (name, sequence) in sequences {
+            header.push(name.len() as u8);
+            header.extend_from_slice(name.as_bytes());
+            header.extend_from_slice(&((16 + index_size + data.len()) as u32).to_le_bytes());
+
+            let n_blocks = runs(sequence, |c| !"ACGTacgt".contains(c));
+            let mask_blocks = runs(sequence, |c| c.is_ascii_lowercase());
+            data.extend_from_slice(&(sequence.len() as u32).to_le_bytes());
+            for blocks in [&n_blocks, &mask_blocks] {
+                data.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+                for value in blocks.iter().map(|b| b.0).chain(blocks.iter().map(|b| b.1)) {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            data.extend_from_slice(&0u32.to_le_bytes());
+            let (length, bytes, _) = PackedSequence::from_text(sequence).into_parts();
+            assert_eq!(length, sequence.len());
+            data.extend(bytes);
+        }
+        header.extend(data);
+        header
+    }
+
+    #[test]
+    fn n_blocks_and_masks()
+    {
+        let file = encode(&[("one", "ACGTNNNNNacgtACGGTTnnAC"), ("two", "NNNNCCCC")]);
+        assert!(is_twobit(&file));
+        let file = TwoBitFile::open(&file).unwrap();
+        assert_eq!(file.names().collect::<Vec<_>>(), ["one", "two"]);
+        assert_eq!(file.length("one").unwrap(), 23);
+
+        // N-blocks come back as N, soft-masked bases as plain bases
+        let records = file.records().unwrap();
+        assert_eq!(records[0].sequence, "ACGTNNNNNACGTACGGTTNNAC");
+        assert_eq!(records[1].sequence, "NNNNCCCC");
+
+        // ranges starting within a byte, cutting N-blocks, and past the end
+        assert_eq!(file.read("one", 2..7).unwrap().to_text(), "GTNNN");
+        assert_eq!(file.read("one", 7..13).unwrap().to_text(), "NNACGT");
+        assert_eq!(file.read("one", 19..40).unwrap().to_text(), "NNAC");
+        assert_eq!(file.read("two", 3..5).unwrap().to_text(), "NC");
+        assert_eq!(file.record("one", Some(9..13)).unwrap().sequence, "ACGT");
+    }
+
+    #[test]
+    fn unknown_and_truncated_sequences()
+    {
+        let bytes = encode(&[("one", "ACGTACGTACGT")]);
+        assert!(TwoBitFile::open(&bytes).unwrap().read("other", 0..4).is_err());
+        let cut = &bytes[..bytes.len() - 1];
+        assert!(TwoBitFile::open(cut).unwrap().read("one", 0..4).is_err());
+        assert!(TwoBitFile::open(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn huge_counts_are_errors()
+    {
+        // the record of "one" starts at 24: length, N-block count, and with no N-blocks the mask block count
+        let bytes = encode(&[("one", "ACGTACGTACGT")]);
+        for at in [24, 28, 32] {
+            let mut bytes = bytes.clone();
+            bytes[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(matches!(TwoBitFile::open(&bytes).unwrap().read("one", 0..4), Err(Error::InvalidTwoBit { .. })), "{}", at);
+        }
+        // a record offset past the end of the file
+        let mut bytes = bytes;
+        bytes[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(TwoBitFile::open(&bytes).unwrap().length("one"), Err(Error::InvalidTwoBit { .. })));
+    }
+}
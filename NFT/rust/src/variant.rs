@@ -0,0 +1,812 @@
+//! Variant calling from a nucleotide alignment of the sample to the reference, and the effect of each variant
+//! on the reference proteins it falls in.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::align::{self, Alignment};
+use crate::amino_acid::AminoAcid;
+use crate::fasta::Record;
+use crate::frame::{Frame, Strand};
+use crate::genetic_code::GeneticCode;
+use crate::input::Slice;
+use crate::nucleotide::{self, Nucleotide};
+use crate::orf::{self, Orf};
+
+// distance from the diagonal searched by the nucleotide alignment, on top of the difference in length
+const BAND: usize = 64;
+// bases per k-mer placing a read on the reference
+const SEED: usize = 16;
+// least distance between two anchors of a global alignment
+const ANCHOR_SPACING: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantKind {
+    /// One base substituted.
+    Snv,
+    /// Several adjacent bases substituted.
+    Mnv,
+    Insertion,
+    Deletion,
+}
+
+/// Effect of a variant on a protein, from the mildest to the most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// The protein is unchanged.
+    Synonymous,
+    /// Residues are substituted, inserted or deleted.
+    Missense,
+    /// The stop codon is lost and translation reads on.
+    StopLoss,
+    /// A stop codon appears early.
+    Nonsense,
+    /// An insertion or deletion that is not a multiple of three bases.
+    Frameshift,
+}
+
+/// Effect of a variant on one reference protein.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrfEffect {
+    /// ID of the record of the reference protein.
+    pub record: String,
+    /// Gene name of an annotated reference protein.
+    pub name: Option<String>,
+    pub frame: Frame,
+    /// Nucleotide range of the reference protein.
+    pub start: usize,
+    pub end: usize,
+    pub effect: Effect,
+    /// 1-based number of the first residue changed, or of the residue hit by a synonymous variant.
+    pub residue: usize,
+    /// Residues of the reference from `residue` on that the variant replaces.
+    pub reference_residues: Vec<AminoAcid>,
+    /// What replaces them. After a stop loss or a frameshift, this is the new protein from `residue` up to and
+    /// including its first stop, which may be missing if translation runs off the end of the sequence.
+    pub alternate_residues: Vec<AminoAcid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Variant {
+    /// ID of the reference record.
+    pub record: String,
+    /// ID of the sample record it was called from.
+    pub sample_record: String,
+    pub kind: VariantKind,
+    /// 0-based offset of the first reference base changed; for an insertion, of the base the new bases go before.
+    pub position: usize,
+    /// 0-based offset of the same place in the sample, on its forward strand, even for a read of the reverse one.
+    pub sample_position: usize,
+    /// Reference bases, empty for an insertion.
+    pub reference: String,
+    /// Sample bases, empty for a deletion.
+    pub alternate: String,
+    /// One entry per reference protein the variant falls in.
+    pub effects: Vec<OrfEffect>,
+}
+
+impl Variant {
+    /// Reference bases replaced, as a forward-strand range.
+    pub fn reference_range(&self) -> Range<usize>
+    {
+        self.position..self.position + self.reference.len()
+    }
+
+    /// Sample bases introduced, as a forward-strand range.
+    pub fn sample_range(&self) -> Range<usize>
+    {
+        self.sample_position..self.sample_position + self.alternate.len()
+    }
+
+    /// Moves a variant called on a record that only holds `slice` of a sequence, and its effects, to the
+    /// coordinates of the whole sequence.
+    pub fn move_to(&mut self, slice: &Slice)
+    {
+        self.position += slice.range.start;
+        for effect in &mut self.effects {
+            effect.frame = slice.frame(effect.frame);
+            effect.start += slice.range.start;
+            effect.end += slice.range.start;
+        }
+    }
+
+    /// Effect on the reference protein `orf`, if the variant falls in it.
+    pub fn effect_for(&self, orf: &Orf) -> Option<&OrfEffect>
+    {
+        self.effects.iter().find(|e| e.record == orf.record && e.frame == orf.frame && e.start == orf.start && e.end == orf.end)
+    }
+
+    pub fn effect_on(&self, orf: &Orf) -> Option<Effect>
+    {
+        self.effect_for(orf).map(|e| e.effect)
+    }
+}
+
+fn to_nucleotides(seq: &str) -> Vec<Nucleotide>
+{
+    seq.chars().map(|c| Nucleotide::from_char(c).unwrap_or(Nucleotide::N)).collect()
+}
+
+fn to_string(bases: &[Nucleotide]) -> String
+{
+    bases.iter().map(|n| n.to_char()).collect()
+}
+
+// what a column of the alignment is; a variant is a run of columns of the same kind
+fn column_kind(sample: &[Nucleotide], reference: &[Nucleotide], column: &align::Column) -> Option<VariantKind>
+{
+    match (column.sample, column.reference) {
+        (Some(s), Some(r)) if sample[s] as u8 & reference[r] as u8 != 0 => None,
+        (Some(_), Some(_)) => Some(VariantKind::Snv),
+        (Some(_), None) => Some(VariantKind::Insertion),
+        (None, Some(_)) => Some(VariantKind::Deletion),
+        (None, None) => None,
+    }
+}
+
+/// A sample record aligned to the reference record it goes with (see align_records).
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    /// Index of the sample record.
+    pub sample: usize,
+    /// Index of the reference record.
+    pub reference: usize,
+    /// Strand of the sample record that matches the reference: reads come from either one.
+    pub strand: Strand,
+    /// Of the sample record to the reference record, in the coordinates of both; on the reverse strand, in those of
+    /// the reverse complement of the sample record (see aligned_record).
+    pub alignment: Alignment,
+}
+
+impl Pairing {
+    /// Forward-strand range of the reference the alignment covers: all of it, unless the sample is a read.
+    pub fn reference_span(&self) -> Range<usize>
+    {
+        let mut covered = self.alignment.columns.iter().filter_map(|c| c.reference);
+        let first = covered.next().unwrap_or(0);
+        first..covered.next_back().unwrap_or(first) + 1
+    }
+
+    /// The sample record as it was aligned: reverse-complemented if it matches the reverse strand of the reference.
+    pub fn aligned_record(&self, record: &Record) -> Record
+    {
+        match self.strand {
+            Strand::Forward => record.clone(),
+            Strand::Reverse => Record { sequence: nucleotide::reverse_complement(&record.sequence), ..record.clone() },
+        }
+    }
+
+    /// Forward-strand range of the sample record, of `length` bases, from a range of the record as it was aligned.
+    pub fn on_sample(&self, range: Range<usize>, length: usize) -> Range<usize>
+    {
+        match self.strand {
+            Strand::Forward => range,
+            Strand::Reverse => length - range.end..length - range.start,
+        }
+    }
+}
+
+/// Nucleotide alignment of `sample` to `reference`, which variants are called from.
+pub fn align_sequences(sample: &str, reference: &str) -> Alignment
+{
+    align_anchored(&to_nucleotides(sample), &to_nucleotides(reference))
+}
+
+// each k-mer of `bases` with no ambiguous base, two bits per base, and where it starts
+fn seeds(bases: &[Nucleotide]) -> Vec<(u32, usize)>
+{
+    let mut seeds: Vec<(u32, usize)> = Vec::new();
+    let (mut kmer, mut length) = (0u32, 0);
+    for (i, base) in bases.iter().enumerate() {
+        match nucleotide::BASES.iter().position(|b| b == base) {
+            Some(code) => {
+                kmer = kmer << 2 | code as u32;
+                length += 1;
+            }
+            None => length = 0,
+        }
+        if length >= SEED {
+            seeds.push((kmer, i + 1 - SEED));
+        }
+    }
+    seeds
+}
+
+// where each k-mer of a reference record is
+fn seed_index(bases: &[Nucleotide]) -> HashMap<u32, Vec<usize>>
+{
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (kmer, position) in seeds(bases) {
+        index.entry(kmer).or_default().push(position);
+    }
+    index
+}
+
+fn reverse_complement(bases: &[Nucleotide]) -> Vec<Nucleotide>
+{
+    bases.iter().rev().map(|n| n.complement()).collect()
+}
+
+// the diagonal (reference position minus read position) most k-mers of a read fall on, and how many do
+fn place(seeds: &[(u32, usize)], index: &HashMap<u32, Vec<usize>>) -> Option<(isize, usize)>
+{
+    let mut diagonals: HashMap<isize, usize> = HashMap::new();
+    for (kmer, offset) in seeds {
+        for &position in index.get(kmer).into_iter().flatten() {
+            *diagonals.entry(position as isize - *offset as isize).or_default() += 1;
+        }
+    }
+    // ties go to the leftmost
+    diagonals.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+}
+
+// same as place, on whichever strand of the read more k-mers fall on the same diagonal; ties go to the forward one
+fn place_either_strand(read: &[Nucleotide], index: &HashMap<u32, Vec<usize>>) -> Option<(isize, usize, Strand)>
+{
+    let forward = place(&seeds(read), index).map(|(diagonal, hits)| (diagonal, hits, Strand::Forward));
+    let reverse = place(&seeds(&reverse_complement(read)), index).map(|(diagonal, hits)| (diagonal, hits, Strand::Reverse));
+    match (forward, reverse) {
+        (Some(forward), Some(reverse)) if reverse.1 > forward.1 => Some(reverse),
+        (None, reverse) => reverse,
+        (forward, _) => forward,
+    }
+}
+
+// local alignment of a read, on the strand that matches (see place_either_strand), to the stretch of `reference` its
+// k-mers fall on, in the coordinates of the whole reference; empty if none does
+fn align_read(read: &[Nucleotide], reference: &[Nucleotide], index: &HashMap<u32, Vec<usize>>) -> (Alignment, Strand)
+{
+    let (diagonal, strand) = match place_either_strand(read, index) {
+        Some((diagonal, _, strand)) => (diagonal, strand),
+        None => return (Alignment::empty(), Strand::Forward),
+    };
+    let read = match strand {
+        Strand::Forward => read.to_vec(),
+        Strand::Reverse => reverse_complement(read),
+    };
+    let start = (diagonal - BAND as isize).clamp(0, reference.len() as isize) as usize;
+    let end = (diagonal + (read.len() + BAND) as isize).clamp(start as isize, reference.len() as isize) as usize;
+    let mut alignment = align::align_nucleotides_local(&read, &reference[start..end], BAND);
+    for column in &mut alignment.columns {
+        column.reference = column.reference.map(|j| j + start);
+    }
+    (alignment, strand)
+}
+
+// the k-mers found once in `bases`, with where they start
+fn unique_seeds(bases: &[Nucleotide]) -> HashMap<u32, Option<usize>>
+{
+    let mut unique: HashMap<u32, Option<usize>> = HashMap::new();
+    for (kmer, position) in seeds(bases) {
+        unique.entry(kmer).and_modify(|found| *found = None).or_insert(Some(position));
+    }
+    unique
+}
+
+// points (sample position, reference position) the two sequences surely go through: the middle of k-mers found
+// once in each, along the longest chain of them in the same order on both, at least ANCHOR_SPACING apart
+fn anchors(sample: &[Nucleotide], reference: &[Nucleotide]) -> Vec<(usize, usize)>
+{
+    let in_reference = unique_seeds(reference);
+    let mut pairs: Vec<(usize, usize)> = unique_seeds(sample).into_iter()
+        .filter_map(|(kmer, s)| Some((s?, (*in_reference.get(&kmer)?)?)))
+        .collect();
+    pairs.sort_unstable();
+
+    // longest chain with increasing reference positions: tails[k] ends the best chain of k + 1 pairs found so far
+    let mut tails: Vec<usize> = Vec::new();
+    let mut before: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (i, &(_, r)) in pairs.iter().enumerate() {
+        let k = tails.partition_point(|&t| pairs[t].1 < r);
+        before[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        }
+        else {
+            tails[k] = i;
+        }
+    }
+    let mut chain: Vec<(usize, usize)> = Vec::new();
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        chain.push(pairs[i]);
+        next = before[i];
+    }
+    chain.reverse();
+
+    let mut anchors: Vec<(usize, usize)> = Vec::new();
+    for (s, r) in chain {
+        if anchors.last().is_none_or(|&(last_s, last_r)| s >= last_s + ANCHOR_SPACING && r >= last_r + ANCHOR_SPACING) {
+            anchors.push((s, r));
+        }
+    }
+    anchors.into_iter().map(|(s, r)| (s + SEED / 2, r + SEED / 2)).collect()
+}
+
+// global alignment of two sequences, split at their anchors: the band of each piece covers the difference in length
+// within that piece only, so a long indel costs its own length instead of widening the band along both sequences
+fn align_anchored(sample: &[Nucleotide], reference: &[Nucleotide]) -> Alignment
+{
+    let mut ends = anchors(sample, reference);
+    ends.push((sample.len(), reference.len()));
+    let mut alignment = Alignment::empty();
+    let (mut s, mut r) = (0, 0);
+    for (s_end, r_end) in ends {
+        let piece = align::align_nucleotides(&sample[s..s_end], &reference[r..r_end], BAND);
+        alignment.score += piece.score;
+        alignment.columns.extend(piece.columns.into_iter().map(|column| align::Column {
+            sample: column.sample.map(|i| i + s),
+            reference: column.reference.map(|j| j + r),
+        }));
+        s = s_end;
+        r = r_end;
+    }
+    alignment
+}
+
+/// Pairs each sample record with a reference record and aligns them. A record goes with the reference record of
+/// the same ID, else with the lone reference record, else with the one it shares the most k-mers with. A record
+/// less than half as long as its reference is a read: it is aligned locally, on the strand most of its k-mers
+/// match, to the part of the reference it comes from, so that variants are only called where it covers the
+/// reference. The same goes the other way for a reference less than half as long as the record, e.g. a region of
+/// a genome. A read none of whose k-mers is in the reference is left unaligned, with no variants.
+pub fn align_records(records: &[Record], ref_records: &[Record]) -> Vec<Pairing>
+{
+    if ref_records.is_empty() {
+        return Vec::new();
+    }
+    let references: Vec<Vec<Nucleotide>> = ref_records.iter().map(|r| to_nucleotides(&r.sequence)).collect();
+    // built the first time a read needs them
+    let mut indexes: Vec<Option<HashMap<u32, Vec<usize>>>> = vec![None; references.len()];
+
+    let mut pairings: Vec<Pairing> = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let bases = to_nucleotides(&record.sequence);
+        let reference = match ref_records.iter().position(|r| r.id == record.id) {
+            Some(r) => r,
+            None if ref_records.len() == 1 => 0,
+            None => {
+                let mut best = (0, 0);
+                for r in 0..references.len() {
+                    let index = indexes[r].get_or_insert_with(|| seed_index(&references[r]));
+                    let hits = place_either_strand(&bases, index).map_or(0, |(_, hits, _)| hits);
+                    if hits > best.1 {
+                        best = (r, hits);
+                    }
+                }
+                best.0
+            }
+        };
+        let (alignment, strand) = if 2 * bases.len() < references[reference].len() {
+            let index = indexes[reference].get_or_insert_with(|| seed_index(&references[reference]));
+            align_read(&bases, &references[reference], index)
+        }
+        else if 2 * references[reference].len() < bases.len() {
+            let (mut alignment, strand) = align_read(&references[reference], &bases, &seed_index(&bases));
+            for column in &mut alignment.columns {
+                *column = align::Column { sample: column.reference, reference: column.sample };
+            }
+            // the reverse complement of the reference against the record is the record's against the reference
+            if strand == Strand::Reverse {
+                let flip = |length: usize| move |i: usize| length - 1 - i;
+                alignment.columns.reverse();
+                for column in &mut alignment.columns {
+                    column.sample = column.sample.map(flip(bases.len()));
+                    column.reference = column.reference.map(flip(references[reference].len()));
+                }
+            }
+            (alignment, strand)
+        }
+        else {
+            (align_anchored(&bases, &references[reference]), Strand::Forward)
+        };
+        pairings.push(Pairing { sample: i, reference, strand, alignment });
+    }
+    pairings
+}
+
+/// Variants of `sample` against `reference`, in reference order. Ambiguous sample bases that can stand for the
+/// reference base are not called; a run of adjacent substitutions is a single MNV.
+pub fn call_variants(record: &str, sample: &str, reference: &str) -> Vec<Variant>
+{
+    let record = |sequence: &str| Record { id: record.to_string(), description: String::new(), sequence: sequence.to_string() };
+    variants_of(&record(sample), &record(reference), &align_sequences(sample, reference))
+}
+
+/// Same as call_variants, from an alignment already made by align_sequences or align_records. Only the part of
+/// the reference the alignment covers is called.
+pub fn variants_of(sample_record: &Record, reference_record: &Record, alignment: &Alignment) -> Vec<Variant>
+{
+    let sample = to_nucleotides(&sample_record.sequence);
+    let reference = to_nucleotides(&reference_record.sequence);
+
+    let mut variants: Vec<Variant> = Vec::new();
+    let columns = &alignment.columns;
+    // next base of each sequence after the columns read so far; a local alignment starts inside both
+    let (mut s, mut r) = columns.first().map_or((0, 0), |c| (c.sample.unwrap_or(0), c.reference.unwrap_or(0)));
+    let mut i = 0;
+    while i < columns.len() {
+        let kind = match column_kind(&sample, &reference, &columns[i]) {
+            Some(kind) => kind,
+            None => {
+                s += 1;
+                r += 1;
+                i += 1;
+                continue;
+            }
+        };
+        let run = columns[i..].iter().take_while(|c| column_kind(&sample, &reference, c) == Some(kind)).count();
+        let (s_len, r_len) = match kind {
+            VariantKind::Insertion => (run, 0),
+            VariantKind::Deletion => (0, run),
+            _ => (run, run),
+        };
+        variants.push(Variant {
+            record: reference_record.id.clone(),
+            sample_record: sample_record.id.clone(),
+            kind: if kind == VariantKind::Snv && run > 1 { VariantKind::Mnv } else { kind },
+            position: r,
+            sample_position: s,
+            reference: to_string(&reference[r..r + r_len]),
+            alternate: to_string(&sample[s..s + s_len]),
+            effects: Vec::new(),
+        });
+        s += s_len;
+        r += r_len;
+        i += run;
+    }
+    variants
+}
+
+fn translate(bases: &[Nucleotide], code: &GeneticCode) -> Vec<AminoAcid>
+{
+    bases.chunks_exact(3).map(|c| code.translate([c[0], c[1], c[2]])).collect()
+}
+
+// bases read past an ORF, in its direction, before the window doubles: a lost stop codon or a frameshift usually
+// reaches a new stop within a few codons, so this costs the distance to that stop, not the length of the record
+const FLANK_WINDOW: usize = 300;
+
+// translate up to and including the first stop codon
+fn translate_to_stop(bases: &[Nucleotide], code: &GeneticCode) -> Vec<AminoAcid>
+{
+    let mut protein: Vec<AminoAcid> = Vec::new();
+    for c in bases.chunks_exact(3) {
+        let aa = code.translate([c[0], c[1], c[2]]);
+        protein.push(aa);
+        if aa == AminoAcid::Stop {
+            break;
+        }
+    }
+    protein
+}
+
+// translation of `coding` up to and including its first stop codon, read on into `flank` as far as needed to reach
+// one: `flank` is a forward-strand range of the bases `bases(range)` gives, read on `strand` from the end of `coding`
+fn translate_on<F>(mut coding: Vec<Nucleotide>, bases: F, flank: Range<usize>, strand: Strand, code: &GeneticCode) -> Vec<AminoAcid>
+    where F: Fn(Range<usize>) -> Vec<Nucleotide>
+{
+    let mut read = 0;
+    loop {
+        let protein = translate_to_stop(&coding, code);
+        if protein.last() == Some(&AminoAcid::Stop) || read == flank.len() {
+            return protein;
+        }
+        let next = (read + FLANK_WINDOW.max(read)).min(flank.len());
+        let window = match strand {
+            Strand::Forward => flank.start + read..flank.start + next,
+            Strand::Reverse => flank.end - next..flank.end - read,
+        };
+        let window = bases(window);
+        let whole = 0..window.len();
+        coding.extend(orf::splice(&window, &[whole], strand));
+        read = next;
+    }
+}
+
+/// Effect of `variant` on the reference protein `orf`, or None if the variant is outside it.
+pub fn effect(variant: &Variant, orf: &Orf, reference: &[Nucleotide], code: &GeneticCode) -> Option<OrfEffect>
+{
+    let range = variant.reference_range();
+    let inside = orf.segments.iter().any(|segment| {
+        if range.is_empty() {
+            // an insertion right before the start codon or right after the stop codon leaves the protein alone
+            segment.start < range.start && range.start < segment.end
+        }
+        else {
+            segment.start < range.end && range.start < segment.end
+        }
+    });
+    if !inside {
+        return None;
+    }
+
+    // the record with the variant applied, read a range at a time, and the ORF's segments moved along
+    let alternate = to_nucleotides(&variant.alternate);
+    let changed = |window: Range<usize>| -> Vec<Nucleotide> {
+        window
+            .map(|i| {
+                if i < range.start {
+                    reference[i]
+                }
+                else if i < range.start + alternate.len() {
+                    alternate[i - range.start]
+                }
+                else {
+                    reference[i - alternate.len() + range.len()]
+                }
+            })
+            .collect()
+    };
+    let changed_length = reference.len() - range.len() + alternate.len();
+    let moved = |position: usize| {
+        if position <= range.start {
+            position
+        }
+        else if position >= range.end {
+            position - range.len() + alternate.len()
+        }
+        else {
+            range.start + (position - range.start).min(alternate.len())
+        }
+    };
+    let segments: Vec<Range<usize>> = orf.segments.iter().map(|s| moved(s.start)..moved(s.end)).collect();
+    let low = segments.iter().map(|s| s.start).min().unwrap_or(0);
+    let high = segments.iter().map(|s| s.end).max().unwrap_or(0);
+    let local: Vec<Range<usize>> = segments.iter().map(|s| s.start - low..s.end - low).collect();
+    let coding = orf::splice(&changed(low..high), &local, orf.strand());
+    // read on past the ORF so that a lost stop codon or a frameshift can reach the next stop
+    let last = segments.last().cloned().unwrap_or(0..0);
+    let flank = match orf.strand() {
+        Strand::Forward => last.end..changed_length,
+        Strand::Reverse => 0..last.start,
+    };
+    let new = translate_on(coding, changed, flank, orf.strand(), code);
+
+    // an annotated CDS may leave out its stop codon (as GTF and Ensembl do): read the reference on to it, so both
+    // proteins end the same way
+    let old_coding = orf::splice(reference, &orf.segments, orf.strand());
+    let mut old = translate(&old_coding, code);
+    if old.last() != Some(&AminoAcid::Stop) {
+        let flank = match orf.strand() {
+            Strand::Forward => orf.segments.last().map_or(0, |s| s.end)..reference.len(),
+            Strand::Reverse => 0..orf.segments.last().map_or(0, |s| s.start),
+        };
+        let rest = old_coding[3 * old.len()..].to_vec();
+        old.extend(translate_on(rest, |window| reference[window].to_vec(), flank, orf.strand(), code));
+    }
+    // the residue at `index` of the reference, none past its end (when the reference runs off without a stop)
+    let old_at = |index: usize| old.get(index).copied().into_iter().collect::<Vec<AminoAcid>>();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let shift = variant.alternate.len() as isize - variant.reference.len() as isize;
+    // length of the new protein if it ends at the same stop codon, stops included
+    let expected = old.len() as isize + shift / 3;
+    let terminated = new.last() == Some(&AminoAcid::Stop);
+    let old_terminated = old.last() == Some(&AminoAcid::Stop);
+
+    let (effect, residue, reference_residues, alternate_residues) = if prefix == old.len() && prefix == new.len() {
+        // same protein: the residue of the first codon the variant is in
+        let codon = (range.start..range.end.max(range.start + 1))
+            .filter_map(|position| orf.coding_offset(position))
+            .min()
+            .map_or(0, |offset| (offset / 3).min(old.len().saturating_sub(1)));
+        (Effect::Synonymous, codon, old_at(codon), old_at(codon))
+    }
+    else if shift % 3 != 0 {
+        (Effect::Frameshift, prefix, old_at(prefix), new[prefix..].to_vec())
+    }
+    else if terminated && (new.len() as isize) < expected {
+        (Effect::Nonsense, prefix, old_at(prefix), vec![AminoAcid::Stop])
+    }
+    else if (old_terminated && !terminated) || (new.len() as isize) > expected {
+        (Effect::StopLoss, prefix, old_at(prefix), new[prefix..].to_vec())
+    }
+    else {
+        // residues substituted, inserted or deleted: leave out what both proteins end with
+        let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+        (Effect::Missense, prefix, old[prefix..old.len() - suffix].to_vec(), new[prefix..new.len() - suffix].to_vec())
+    };
+
+    Some(OrfEffect {
+        record: orf.record.clone(),
+        name: orf.name.clone(),
+        frame: orf.frame,
+        start: orf.start,
+        end: orf.end,
+        effect,
+        residue: residue + 1,
+        reference_residues,
+        alternate_residues,
+    })
+}
+
+/// Variants of each sample record against its reference record, from their pairing in `pairings` (see
+/// align_records), with their effect on the reference proteins `ref_enc`.
+pub fn call_records(records: &[Record], ref_records: &[Record], pairings: &[Pairing], ref_enc: &[Orf], code: &GeneticCode) -> Vec<Variant>
+{
+    let references: Vec<Vec<Nucleotide>> = ref_records.iter().map(|r| to_nucleotides(&r.sequence)).collect();
+    let mut variants: Vec<Variant> = Vec::new();
+    for pairing in pairings {
+        let (record, ref_record) = (&records[pairing.sample], &ref_records[pairing.reference]);
+        let reference = &references[pairing.reference];
+        let orfs: Vec<&Orf> = ref_enc.iter().filter(|orf| orf.record == ref_record.id).collect();
+        let aligned = pairing.aligned_record(record);
+        for mut variant in variants_of(&aligned, ref_record, &pairing.alignment) {
+            variant.sample_position = pairing.on_sample(variant.sample_range(), aligned.sequence.len()).start;
+            for orf in &orfs {
+                variant.effects.extend(effect(&variant, orf, reference, code));
+            }
+            variants.push(variant);
+        }
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{genome, record};
+
+    // ATG AAA CGT GGC TTT CTG GAA TAA and a flank: Met Lys Arg Gly Phe Leu Glu, its stop codon at 21..24
+    const REFERENCE: &str = "ATGAAACGTGGCTTTCTGGAATAAGCGCGC";
+
+    fn reverse_complement(seq: &str) -> String
+    {
+        to_nucleotides(seq).iter().rev().map(|n| n.complement().to_char()).collect()
+    }
+
+    // the single variant of `sample` against `reference`, with its effect on the CDS `segment`
+    fn effect_of(sample: &str, reference: &str, segment: Range<usize>, strand: Strand) -> Option<OrfEffect>
+    {
+        let code = GeneticCode::from_id(1).unwrap();
+        let bases = to_nucleotides(reference);
+        let cds = orf::from_segments("ref", &bases, vec![segment], strand, true, code, None);
+        let variants = call_variants("ref", sample, reference);
+        assert_eq!(variants.len(), 1);
+        effect(&variants[0], &cds, &bases, code)
+    }
+
+    fn substituted(position: usize, base: char) -> String
+    {
+        let mut sample: Vec<char> = REFERENCE.chars().collect();
+        sample[position] = base;
+        sample.into_iter().collect()
+    }
+
+    #[test]
+    fn cds_with_and_without_stop_codon()
+    {
+        for cds in [0..24, 0..21] {
+            // AAA > AAG
+            let synonymous = effect_of(&substituted(5, 'G'), REFERENCE, cds.clone(), Strand::Forward).unwrap();
+            assert_eq!((synonymous.effect, synonymous.residue), (Effect::Synonymous, 2));
+            // GAA > GAG, in the last codon of a CDS without its stop
+            let synonymous = effect_of(&substituted(20, 'G'), REFERENCE, cds.clone(), Strand::Forward).unwrap();
+            assert_eq!((synonymous.effect, synonymous.residue), (Effect::Synonymous, 7));
+            assert_eq!(synonymous.reference_residues, vec![AminoAcid::Glu]);
+            // CGT > TGT
+            let missense = effect_of(&substituted(6, 'T'), REFERENCE, cds.clone(), Strand::Forward).unwrap();
+            assert_eq!((missense.effect, missense.residue), (Effect::Missense, 3));
+            assert_eq!(missense.reference_residues, vec![AminoAcid::Arg]);
+            assert_eq!(missense.alternate_residues, vec![AminoAcid::Cys]);
+            // AAA > TAA
+            let nonsense = effect_of(&substituted(3, 'T'), REFERENCE, cds.clone(), Strand::Forward).unwrap();
+            assert_eq!((nonsense.effect, nonsense.residue), (Effect::Nonsense, 2));
+            // GGC deleted
+            let sample = format!("{}{}", &REFERENCE[..9], &REFERENCE[12..]);
+            let deletion = effect_of(&sample, REFERENCE, cds.clone(), Strand::Forward).unwrap();
+            assert_eq!(deletion.effect, Effect::Missense);
+            assert_eq!(deletion.alternate_residues, Vec::new());
+        }
+        // TAA > CAA reads on into the flank, and only touches the CDS that includes the stop codon
+        let stop_loss = effect_of(&substituted(21, 'C'), REFERENCE, 0..24, Strand::Forward).unwrap();
+        assert_eq!((stop_loss.effect, stop_loss.residue), (Effect::StopLoss, 8));
+        assert_eq!(stop_loss.reference_residues, vec![AminoAcid::Stop]);
+        assert!(effect_of(&substituted(21, 'C'), REFERENCE, 0..21, Strand::Forward).is_none());
+    }
+
+    #[test]
+    fn reverse_cds_without_stop_codon()
+    {
+        let reference = reverse_complement(REFERENCE);
+        let length = reference.len();
+        // the last codon, GAA, is at 18..21 of REFERENCE
+        let sample = reverse_complement(&substituted(20, 'G'));
+        let synonymous = effect_of(&sample, &reference, length - 21..length, Strand::Reverse).unwrap();
+        assert_eq!((synonymous.effect, synonymous.residue), (Effect::Synonymous, 7));
+        let sample = reverse_complement(&substituted(6, 'T'));
+        let missense = effect_of(&sample, &reference, length - 21..length, Strand::Reverse).unwrap();
+        assert_eq!((missense.effect, missense.alternate_residues), (Effect::Missense, vec![AminoAcid::Cys]));
+    }
+
+    #[test]
+    fn stop_loss_reads_on_to_a_distant_stop()
+    {
+        // Met, 10 Ala and a stop, then 400 more Ala codons before the next stop in frame, past the first window
+        let reference = format!("ATG{}TAA{}TAACC", "GCA".repeat(10), "GCA".repeat(400));
+        let cds = 0..36;
+        let sample = reference.replacen("TAA", "CAA", 1);
+        let lost = effect_of(&sample, &reference, cds.clone(), Strand::Forward).unwrap();
+        assert_eq!((lost.effect, lost.residue, lost.alternate_residues.len()), (Effect::StopLoss, 12, 402));
+        assert_eq!(lost.alternate_residues.last(), Some(&AminoAcid::Stop));
+
+        let length = reference.len();
+        let reverse = effect_of(&reverse_complement(&sample), &reverse_complement(&reference), length - cds.end..length, Strand::Reverse).unwrap();
+        assert_eq!((reverse.effect, reverse.residue, reverse.alternate_residues), (lost.effect, lost.residue, lost.alternate_residues));
+    }
+
+    #[test]
+    fn long_deletion_is_aligned_between_anchors()
+    {
+        // without anchors, the band would be 10,000 bases wide along all 190,000 rows
+        let reference = genome(200_000, 4);
+        let deleted = format!("{}{}", &reference[..50_000], &reference[60_000..]);
+        let mut sample = deleted.clone();
+        sample.replace_range(150_000..150_001, if &sample[150_000..150_001] == "A" { "C" } else { "A" });
+        let variants = call_variants("chr", &sample, &reference);
+        let called: Vec<(VariantKind, usize)> = variants.iter().map(|v| (v.kind, v.reference.len())).collect();
+        assert_eq!(called, vec![(VariantKind::Deletion, 10_000), (VariantKind::Snv, 1)]);
+        // the same deletion, wherever it lands in the bases it could shift over
+        let at = variants[0].position;
+        assert_eq!(format!("{}{}", &reference[..at], &reference[at + 10_000..]), deleted);
+        assert_eq!(variants[1].position, 160_000);
+    }
+
+    #[test]
+    fn reads_are_aligned_where_they_come_from()
+    {
+        let code = GeneticCode::from_id(1).unwrap();
+        let reference = genome(3000, 1);
+        let mut read = reference[2000..2300].to_string();
+        read.replace_range(100..101, if &read[100..101] == "A" { "C" } else { "A" });
+        let records = [record("read1", &reference[500..800]), record("read2", &read), record("read3", &reverse_complement(&read))];
+        let pairings = align_records(&records, &[record("chr", &reference)]);
+        assert_eq!(pairings.iter().map(|p| (p.sample, p.reference)).collect::<Vec<_>>(), vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(pairings.iter().map(|p| p.strand).collect::<Vec<_>>(), vec![Strand::Forward, Strand::Forward, Strand::Reverse]);
+        assert_eq!(pairings[0].reference_span(), 500..800);
+        assert_eq!(pairings[2].reference_span(), 2000..2300);
+
+        // no deletion of the rest of the genome, and the other reads are called too, the third one on the reverse strand
+        let variants = call_records(&records, &[record("chr", &reference)], &pairings, &[], code);
+        let called: Vec<(&str, VariantKind, usize, usize)> = variants.iter()
+            .map(|v| (v.sample_record.as_str(), v.kind, v.position, v.sample_position))
+            .collect();
+        assert_eq!(called, vec![("read2", VariantKind::Snv, 2100, 100), ("read3", VariantKind::Snv, 2100, 199)]);
+        assert_eq!(variants[0].alternate, variants[1].alternate);
+    }
+
+    #[test]
+    fn records_pair_by_id_then_by_kmers()
+    {
+        let (a, b) = (genome(2000, 2), genome(2000, 3));
+        let references = [record("a", &a), record("b", &b)];
+        let records = [record("b", &b), record("read", &a[700..1000]), record("a", &a)];
+        let pairings = align_records(&records, &references);
+        assert_eq!(pairings.iter().map(|p| p.reference).collect::<Vec<_>>(), vec![1, 0, 0]);
+        assert!(pairings.iter().all(|p| p.alignment.columns.iter().all(|c| c.sample.is_some() && c.reference.is_some())));
+    }
+
+    #[test]
+    fn effects_are_on_the_protein_of_their_record()
+    {
+        let code = GeneticCode::from_id(1).unwrap();
+        // two records with the same protein at the same place; only the first one changes
+        let references = [record("a", REFERENCE), record("b", REFERENCE)];
+        let records = [record("a", &substituted(6, 'T')), record("b", REFERENCE)];
+        let bases = to_nucleotides(REFERENCE);
+        let cds = 0..24;
+        let ref_enc: Vec<Orf> = ["a", "b"].iter()
+            .map(|id| orf::from_segments(id, &bases, vec![cds.clone()], Strand::Forward, true, code, None))
+            .collect();
+        let variants = call_records(&records, &references, &align_records(&records, &references), &ref_enc, code);
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].effects.len(), 1);
+        assert_eq!(variants[0].effect_on(&ref_enc[0]), Some(Effect::Missense));
+        assert_eq!(variants[0].effect_on(&ref_enc[1]), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! VCF 4.3 (https://samtools.github.io/hts-specs/VCFv4.3.pdf): output of the variants called against the
+//! reference, and input of a list of variants to apply to the reference.
+//! Insertions and deletions are written the VCF way, with the reference base before them as an anchor.
+
+use std::fmt::Write;
+
+use crate::amino_acid::AminoAcid;
+use crate::fasta::Record;
+use crate::variant::{Effect, OrfEffect, Variant};
+use crate::{Error, Result};
+
+// CHROM of a record without an ID; VCF does not allow an empty one
+const UNNAMED: &str = "sequence";
+
+fn chrom(id: &str) -> &str
+{
+    if id.is_empty() {
+        UNNAMED
+    }
+    else {
+        id
+    }
+}
+
+fn consequence(effect: Effect) -> &'static str
+{
+    match effect {
+        Effect::Synonymous => "synonymous",
+        Effect::Missense => "missense",
+        Effect::StopLoss => "stop_loss",
+        Effect::Nonsense => "nonsense",
+        Effect::Frameshift => "frameshift",
+    }
+}
+
+fn letters(residues: &[AminoAcid]) -> String
+{
+    residues.iter().map(|aa| aa.one_letter()).collect()
+}
+
+// the amino-acid change in one-letter codes, e.g. "K6E", "K6K", "G9del", "4insK", "*27Y" or "P4fs"
+fn amino_acid_change(effect: &OrfEffect) -> String
+{
+    let reference = letters(&effect.reference_residues);
+    if reference.is_empty() {
+        return format!("{}ins{}", effect.residue, letters(&effect.alternate_residues));
+    }
+    let alternate = match effect.effect {
+        Effect::Frameshift => "fs".to_string(),
+        Effect::StopLoss => letters(&effect.alternate_residues[..1.min(effect.alternate_residues.len())]),
+        _ if effect.alternate_residues.is_empty() => "del".to_string(),
+        _ => letters(&effect.alternate_residues),
+    };
+    format!("{}{}{}", reference, effect.residue, alternate)
+}
+
+// POS, REF and ALT of a variant; `bases` is the reference record it was called on
+fn anchored(variant: &Variant, bases: &str) -> (usize, String, String)
+{
+    if !variant.reference.is_empty() && !variant.alternate.is_empty() {
+        return (variant.position + 1, variant.reference.clone(), variant.alternate.clone());
+    }
+    let base = |i: usize| bases.get(i..i + 1).unwrap_or("N").to_ascii_uppercase();
+    if variant.position > 0 {
+        let anchor = base(variant.position - 1);
+        (variant.position, anchor.clone() + &variant.reference, anchor + &variant.alternate)
+    }
+    else {
+        // nothing before the first base: anchor on the base after the variant instead
+        let anchor = base(variant.reference.len());
+        (1, variant.reference.clone() + &anchor, variant.alternate.clone() + &anchor)
+    }
+}
+
+/// Writes `variants` as a VCF file, in reference order. `references` are the reference records they were called
+/// on, for the contig lines and the anchor bases of insertions and deletions. A variant called from several sample
+/// records, e.g. overlapping reads, is written once. VCF 4.3 only allows A, C, G, T and N in ALT, so a call
+/// with an ambiguous sample base that isn't the reference base, e.g. R over C, is left out.
+pub fn write(variants: &[Variant], references: &[Record]) -> String
+{
+    let mut variants: Vec<&Variant> = variants.iter().collect();
+    let record_index = |variant: &Variant| references.iter().position(|r| r.id == variant.record);
+    variants.sort_by(|a, b| {
+        record_index(a).cmp(&record_index(b))
+            .then(a.position.cmp(&b.position))
+            .then(a.reference.cmp(&b.reference))
+            .then(a.alternate.cmp(&b.alternate))
+    });
+    variants.dedup_by(|a, b| a.record == b.record && a.position == b.position && a.reference == b.reference && a.alternate == b.alternate);
+
+    let mut vcf = String::new();
+    vcf.push_str("##fileformat=VCFv4.3\n");
+    vcf.push_str("##source=dna_visualizer\n");
+    for record in references {
+        let _ = writeln!(vcf, "##contig=<ID={},length={}>", chrom(&record.id), record.sequence.len());
+    }
+    vcf.push_str("##INFO=<ID=ORF,Number=.,Type=String,Description=\"Reference ORFs hit, as gene name if annotated, else as frame:start-end (1-based, inclusive)\">\n");
+    vcf.push_str("##INFO=<ID=AA,Number=.,Type=String,Description=\"Amino-acid change in each ORF\">\n");
+    vcf.push_str("##INFO=<ID=CSQ,Number=.,Type=String,Description=\"Consequence in each ORF: synonymous, missense, nonsense, frameshift or stop_loss\">\n");
+    vcf.push_str("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n");
+
+    for variant in variants {
+        let bases = match references.iter().find(|r| r.id == variant.record) {
+            Some(record) => &record.sequence,
+            None => continue,
+        };
+        let (pos, reference, alternate) = anchored(variant, bases);
+        if !alternate.bytes().all(|b| b"ACGTN".contains(&b.to_ascii_uppercase())) {
+            continue;
+        }
+
+        let info = if variant.effects.is_empty() {
+            ".".to_string()
+        }
+        else {
+            let orfs: Vec<String> = variant.effects.iter()
+                .map(|e| match &e.name {
+                    Some(name) => name.clone(),
+                    None => format!("{}:{}-{}", e.frame, e.start + 1, e.end),
+                })
+                .collect();
+            let changes: Vec<String> = variant.effects.iter().map(amino_acid_change).collect();
+            let consequences: Vec<&str> = variant.effects.iter().map(|e| consequence(e.effect)).collect();
+            format!("ORF={};AA={};CSQ={}", orfs.join(","), changes.join(","), consequences.join(","))
+        };
+        let _ = writeln!(vcf, "{}\t{}\t.\t{}\t{}\t.\t.\t{}", chrom(&variant.record), pos, reference, alternate, info);
+    }
+    vcf
+}
+
+/// One data line of a VCF file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcfRecord {
+    pub chrom: String,
+    /// 1-based position of the first REF base.
+    pub position: usize,
+    pub reference: String,
+    /// ALT alleles; only the first one is applied.
+    pub alternates: Vec<String>,
+    pub filter: String,
+    /// Line of the record in the file, from 1.
+    pub line: usize,
+}
+
+impl VcfRecord {
+    /// Records with a FILTER other than PASS or "." are not applied.
+    pub fn passes(&self) -> bool
+    {
+        self.filter == "PASS" || self.filter == "."
+    }
+}
+
+fn is_bases(allele: &str) -> bool
+{
+    !allele.is_empty() && allele.chars().all(|c| crate::nucleotide::Nucleotide::from_char(c).is_some())
+}
+
+/// Reads the data lines of a VCF file; meta-information and header lines are skipped. Only sequence
+/// alleles are accepted: symbolic alleles such as <DEL> and breakends are an error.
+pub fn parse(text: &str) -> Result<Vec<VcfRecord>>
+{
+    let mut records: Vec<VcfRecord> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| Error::InvalidVcf { line: i + 1, message };
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            return Err(invalid(format!("expected at least 5 tab-separated columns, found {}", fields.len())));
+        }
+        let position: usize = fields[1].parse().map_err(|_| invalid(format!("POS '{}' is not a number", fields[1])))?;
+        if position == 0 {
+            return Err(invalid("POS 0 is only allowed for telomeres".to_string()));
+        }
+        let reference = fields[3].to_ascii_uppercase();
+        if !is_bases(&reference) {
+            return Err(invalid(format!("REF '{}' is not a sequence of bases", fields[3])));
+        }
+        let mut alternates: Vec<String> = Vec::new();
+        for allele in fields[4].split(',') {
+            // '.' means no alternate allele at all
+            if allele == "." {
+                continue;
+            }
+            if !is_bases(allele) {
+                return Err(invalid(format!("ALT '{}' is not a sequence of bases", allele)));
+            }
+            alternates.push(allele.to_ascii_uppercase());
+        }
+
+        records.push(VcfRecord {
+            chrom: fields[0].to_string(),
+            position,
+            reference,
+            alternates,
+            filter: fields.get(6).unwrap_or(&".").to_string(),
+            line: i + 1,
+        });
+    }
+    Ok(records)
+}
+
+/// Applies the first ALT allele of each passing VCF record to the reference record named by its CHROM.
+/// REF alleles must match the reference, and no two records may change the same bases. A lone unnamed
+/// reference, e.g. a raw sequence, takes every CHROM.
+pub fn apply(references: &[Record], variants: &[VcfRecord]) -> Result<Vec<Record>>
+{
+    let lone_unnamed = references.len() == 1 && references[0].id.is_empty();
+    let mut by_record: Vec<Vec<&VcfRecord>> = vec![Vec::new(); references.len()];
+    for variant in variants.iter().filter(|v| v.passes() && !v.alternates.is_empty()) {
+        let index = if lone_unnamed {
+            Some(0)
+        }
+        else {
+            references.iter().position(|r| r.id == variant.chrom)
+        };
+        match index {
+            Some(index) => by_record[index].push(variant),
+            None => return Err(Error::UnknownContig { contig: variant.chrom.clone() }),
+        }
+    }
+
+    let mut consensus: Vec<Record> = Vec::new();
+    for (record, mut variants) in references.iter().zip(by_record) {
+        variants.sort_by_key(|v| v.position);
+
+        let bases = &record.sequence;
+        let mut sequence = String::with_capacity(bases.len());
+        // 0-based end of the reference bases already copied or replaced
+        let mut copied = 0;
+        let mut previous = 0;
+        for variant in variants {
+            let start = variant.position - 1;
+            let end = start + variant.reference.len();
+            if start < copied {
+                return Err(Error::OverlappingVariants { record: record.id.clone(), position: variant.position, previous });
+            }
+            let found = bases.get(start..end.min(bases.len())).unwrap_or("");
+            if !found.eq_ignore_ascii_case(&variant.reference) {
+                return Err(Error::ReferenceMismatch {
+                    record: record.id.clone(),
+                    position: variant.position,
+                    expected: variant.reference.clone(),
+                    found: found.to_string(),
+                });
+            }
+            sequence.push_str(&bases[copied..start]);
+            sequence.push_str(&variant.alternates[0]);
+            copied = end;
+            previous = variant.position;
+        }
+        sequence.push_str(&bases[copied..]);
+
+        consensus.push(Record { id: record.id.clone(), description: record.description.clone(), sequence });
+    }
+    Ok(consensus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{genome, record};
+    use crate::variant::call_variants;
+
+    fn line(position: usize, reference: &str, alternate: &str) -> String
+    {
+        format!("chr\t{}\t.\t{}\t{}\t.\tPASS\t.\n", position, reference, alternate)
+    }
+
+    #[test]
+    fn applied_variants_are_called_back()
+    {
+        let bases = genome(600, 7);
+        let references = [record("chr", &bases)];
+        // a substitution, a deletion of 3 bases and an insertion of 2, anchored on the base before them; the
+        // inserted base differs from those around it, so the insertion can't be shifted either way
+        let inserted = ['A', 'C', 'G', 'T'].iter().find(|&&c| !bases[449..451].contains(c)).unwrap().to_string().repeat(2);
+        let text = "##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n".to_string()
+            + &line(101, &bases[100..101], if &bases[100..101] == "A" { "C" } else { "A" })
+            + &line(300, &bases[299..303], &bases[299..300])
+            + &line(450, &bases[449..450], &(bases[449..450].to_string() + &inserted));
+        let applied = parse(&text).unwrap();
+        let sample = apply(&references, &applied).unwrap();
+        assert_eq!(sample[0].sequence.len(), 600 - 3 + 2);
+
+        let variants = call_variants("chr", &sample[0].sequence, &bases);
+        let called = parse(&write(&variants, &references)).unwrap();
+        let alleles = |records: &[VcfRecord]| -> Vec<(usize, String, Vec<String>)> {
+            records.iter().map(|r| (r.position, r.reference.clone(), r.alternates.clone())).collect()
+        };
+        assert_eq!(alleles(&called), alleles(&applied));
+        assert_eq!(apply(&references, &called).unwrap()[0].sequence, sample[0].sequence);
+    }
+
+    #[test]
+    fn ambiguous_alternates_are_left_out()
+    {
+        let bases = genome(300, 7);
+        let references = [record("chr", &bases)];
+        // R stands for A or G, so it is only a call over C or T, and Y the other way round
+        let plain = if &bases[50..51] == "A" { "C" } else { "A" };
+        let ambiguous = if "AG".contains(&bases[200..201]) { "Y" } else { "R" };
+        let sample = bases[..50].to_string() + plain + &bases[51..200] + ambiguous + &bases[201..];
+        let variants = call_variants("chr", &sample, &bases);
+        assert_eq!(variants.len(), 2);
+
+        let called = parse(&write(&variants, &references)).unwrap();
+        assert_eq!(called.len(), 1);
+        assert_eq!((called[0].position, called[0].alternates.clone()), (51, vec![plain.to_string()]));
+    }
+
+    #[test]
+    fn mismatched_and_overlapping_records_are_rejected()
+    {
+        let bases = genome(100, 7);
+        let references = [record("chr", &bases)];
+        let wrong = if &bases[9..10] == "A" { "C" } else { "A" };
+        let mismatch = parse(&line(10, wrong, &bases[9..10])).unwrap();
+        assert!(matches!(apply(&references, &mismatch), Err(Error::ReferenceMismatch { position: 10, .. })));
+
+        let overlapping = parse(&(line(10, &bases[9..12], &bases[9..10]) + &line(11, &bases[10..11], wrong))).unwrap();
+        assert!(matches!(apply(&references, &overlapping), Err(Error::OverlappingVariants { position: 11, previous: 10, .. })));
+    }
+}